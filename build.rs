@@ -0,0 +1,38 @@
+fn main() {
+    // c-header feature：用 cbindgen 生成 C 头文件
+    #[cfg(feature = "c-header")]
+    generate_header();
+
+    // bundled feature：把 emoji 图片包编译进可执行文件
+    // 默认使用 assets/emoji_pack.zip，可用环境变量 EPUBEMOJIX_BUNDLE 指定其他路径
+    println!("cargo:rerun-if-env-changed=EPUBEMOJIX_BUNDLE");
+    if std::env::var_os("CARGO_FEATURE_BUNDLED").is_none() {
+        return;
+    }
+    let manifest_dir = std::path::PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let pack = std::env::var("EPUBEMOJIX_BUNDLE")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|_| manifest_dir.join("assets").join("emoji_pack.zip"));
+    let pack = if pack.is_absolute() { pack } else { manifest_dir.join(pack) };
+    if !pack.exists() {
+        panic!(
+            "bundled feature 需要 emoji 图片包: {}\n可先运行 `EpubEmojiX cache export assets/emoji_pack.zip` 生成，或设置 EPUBEMOJIX_BUNDLE",
+            pack.display()
+        );
+    }
+    println!("cargo:rerun-if-changed={}", pack.display());
+    println!("cargo:rustc-env=EPUBEMOJIX_BUNDLE_PATH={}", pack.display());
+}
+
+#[cfg(feature = "c-header")]
+fn generate_header() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).expect("读取 cbindgen.toml 失败");
+    std::fs::create_dir_all(format!("{}/include", crate_dir)).expect("创建 include 目录失败");
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("生成 C 头文件失败")
+        .write_to_file(format!("{}/include/epubemojix.h", crate_dir));
+}
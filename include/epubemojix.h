@@ -0,0 +1,246 @@
+/* 由 cbindgen 生成（cargo build --features c-header），请勿手动修改 */
+
+#ifndef EPUBEMOJIX_H
+#define EPUBEMOJIX_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * 当前 ABI 版本
+ */
+#define EPUBEMOJIX_ABI_VERSION 1
+
+/**
+ * 成功
+ */
+#define EPUBEMOJIX_OK 0
+
+/**
+ * 处理失败（读写、解析 epub 等）
+ */
+#define EPUBEMOJIX_ERROR 1
+
+/**
+ * 配置文件或选项无效
+ */
+#define EPUBEMOJIX_CONFIG_ERROR 2
+
+/**
+ * 已通过取消标志取消
+ */
+#define EPUBEMOJIX_CANCELLED 3
+
+/**
+ * 参数无效（空指针、ABI 版本不支持等）
+ */
+#define EPUBEMOJIX_INVALID_ARGUMENT 4
+
+/**
+ * 取消标志，可在其他线程调用 `EpubEmojiX_cancel_token_cancel`
+ */
+typedef struct EpubEmojiXCancelToken EpubEmojiXCancelToken;
+
+/**
+ * 进度信息，每处理完一个 xhtml/html 文件回调一次
+ */
+typedef struct EpubEmojiXProgress {
+  /**
+   * 刚处理完的文件（epub 内路径），只在回调期间有效
+   */
+  const char *file;
+  uint32_t files_done;
+  uint32_t files_total;
+  /**
+   * 到目前为止替换的 emoji 数
+   */
+  uint32_t emoji_count;
+} EpubEmojiXProgress;
+
+/**
+ * 进度回调，user_data 为 [`EpubEmojiXOptions::user_data`]
+ */
+typedef void (*EpubEmojiXProgressCallback)(const struct EpubEmojiXProgress *progress,
+                                           void *user_data);
+
+/**
+ * 处理选项，字符串均为 UTF-8，NULL 表示使用默认值
+ */
+typedef struct EpubEmojiXOptions {
+  /**
+   * 调用方头文件中的 EPUBEMOJIX_ABI_VERSION，不能为 0
+   */
+  uint32_t abi_version;
+  /**
+   * 调用方的 sizeof(EpubEmojiXOptions)；超出该大小的字段使用默认值，多出的字段忽略
+   */
+  uint32_t struct_size;
+  /**
+   * 配置文件路径，先应用配置文件，再应用下面的字段
+   */
+  const char *config_path;
+  /**
+   * 图片集：twemoji、twemoji-fork、noto、openmoji
+   */
+  const char *provider;
+  /**
+   * 图片集版本（git 标签或分支）
+   */
+  const char *provider_version;
+  /**
+   * 图片尺寸，0 为默认
+   */
+  uint32_t size;
+  /**
+   * 图片缓存目录
+   */
+  const char *cache_dir;
+  /**
+   * 非 0 时离线，只使用缓存中已有的图片
+   */
+  uint32_t offline;
+  /**
+   * img 标签的 style 属性
+   */
+  const char *img_style;
+  /**
+   * 非 0 时默认文本显示的符号（❤、© 等）不带 FE0F 也替换
+   */
+  uint32_t replace_text_default;
+  /**
+   * 非 0 时忽略 FE0E（文本显示）
+   */
+  uint32_t ignore_text_selector;
+  /**
+   * 只替换高于该版本的 emoji，如 "13.0"
+   */
+  const char *min_version;
+  /**
+   * 包含规则，逗号分隔，格式同配置文件 include
+   */
+  const char *include;
+  /**
+   * 排除规则，逗号分隔，格式同配置文件 exclude
+   */
+  const char *exclude;
+  EpubEmojiXProgressCallback progress;
+  void *user_data;
+  /**
+   * 取消标志，可为 NULL
+   */
+  const struct EpubEmojiXCancelToken *cancel;
+} EpubEmojiXOptions;
+
+
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * 可供 FFI 调用的接口示例
+ *
+ * # Safety
+ * `input_path`、`output_path` 必须是有效的以 NUL 结尾的 C 字符串。
+ */
+int32_t EpubEmojiX_replace_emoji_in_epub(const char *input_path, const char *output_path);
+
+/**
+ * 支持 FFI 调用的接口，带 emoji_source/emoji_dir
+ *
+ * emoji_source：0 在线（缺图时下载），1 离线（只用缓存）；
+ * emoji_dir：缓存目录，传空指针使用 exe 所在目录下的 emoji_img。
+ *
+ * # Safety
+ * `input_path`、`output_path` 必须是有效的 C 字符串，`emoji_dir` 为空或有效的 C 字符串。
+ */
+int32_t EpubEmojiX_replace_emoji_in_epub_with_mode(const char *input_path,
+                                                   const char *output_path,
+                                                   uint32_t emoji_source,
+                                                   const char *emoji_dir);
+
+/**
+ * 支持 FFI 调用的接口，按配置文件设置图片来源、识别策略、包含/排除规则等
+ *
+ * config_path 为空指针或空字符串时使用默认选项，配置格式见 [`config`]。
+ *
+ * # Safety
+ * `input_path`、`output_path` 必须是有效的 C 字符串，`config_path` 为空或有效的 C 字符串。
+ */
+int32_t EpubEmojiX_replace_emoji_in_epub_with_config(const char *input_path,
+                                                     const char *output_path,
+                                                     const char *config_path);
+
+/**
+ * 返回库的 ABI 版本
+ */
+uint32_t EpubEmojiX_abi_version(void);
+
+/**
+ * 用默认值填充 options
+ *
+ * # Safety
+ * `options` 必须指向可写的 EpubEmojiXOptions。
+ */
+void EpubEmojiX_options_init(struct EpubEmojiXOptions *options);
+
+/**
+ * 创建取消标志，用 `EpubEmojiX_cancel_token_free` 释放
+ */
+struct EpubEmojiXCancelToken *EpubEmojiX_cancel_token_new(void);
+
+/**
+ * 请求取消，正在进行的处理会在下一个文件前停止并返回 EPUBEMOJIX_CANCELLED
+ *
+ * # Safety
+ * `token` 必须为 `EpubEmojiX_cancel_token_new` 返回且尚未释放的指针。
+ */
+void EpubEmojiX_cancel_token_cancel(const struct EpubEmojiXCancelToken *token);
+
+/**
+ * 释放取消标志
+ *
+ * # Safety
+ * `token` 必须为 `EpubEmojiX_cancel_token_new` 返回的指针，且不再被使用。
+ */
+void EpubEmojiX_cancel_token_free(struct EpubEmojiXCancelToken *token);
+
+/**
+ * 处理 epub 文件
+ *
+ * # Safety
+ * `input_path`、`output_path` 必须是有效的 C 字符串；`options` 为 NULL 或指向已初始化的 EpubEmojiXOptions。
+ */
+int32_t EpubEmojiX_process_file(const char *input_path,
+                                const char *output_path,
+                                const struct EpubEmojiXOptions *options);
+
+/**
+ * 处理内存中的 epub，结果写入 *out_data/*out_len，用 `EpubEmojiX_free_buffer` 释放
+ *
+ * # Safety
+ * `input` 必须指向 `input_len` 字节的可读内存；`out_data`、`out_len` 必须可写；
+ * `options` 为 NULL 或指向已初始化的 EpubEmojiXOptions。
+ */
+int32_t EpubEmojiX_process_buffer(const uint8_t *input,
+                                  size_t input_len,
+                                  uint8_t **out_data,
+                                  size_t *out_len,
+                                  const struct EpubEmojiXOptions *options);
+
+/**
+ * 释放 `EpubEmojiX_process_buffer` 返回的缓冲区
+ *
+ * # Safety
+ * `data`、`len` 必须为 `EpubEmojiX_process_buffer` 返回的值，且只释放一次。
+ */
+void EpubEmojiX_free_buffer(uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* EPUBEMOJIX_H */
@@ -0,0 +1,152 @@
+//! 编译进可执行文件的 emoji 图片包（`bundled` feature）
+//!
+//! 图片包格式与 `cache export` 导出的一致，构建时由 build.rs 指定路径。
+//! 启动时只建立 code -> 文件序号的索引，图片在用到时才从内置 zip 中解压。
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::sync::OnceLock;
+use zip::ZipArchive;
+
+use crate::naming;
+use crate::provider::Provider;
+
+static PACK: &[u8] = include_bytes!(env!("EPUBEMOJIX_BUNDLE_PATH"));
+
+const MANIFEST: &str = "emojipack.txt";
+
+struct Bundle {
+    provider: Provider,
+    size: u32,
+    /// 清单中没有 version 时为 None，即导出时使用的图片集默认版本
+    version: Option<String>,
+    archive: ZipArchive<Cursor<&'static [u8]>>,
+    /// code -> zip 中的文件序号
+    index: HashMap<String, usize>,
+}
+
+impl Bundle {
+    fn parse(pack: &'static [u8]) -> Result<Bundle, String> {
+        let mut archive = ZipArchive::new(Cursor::new(pack)).map_err(|e| format!("解析内置图片包失败: {}", e))?;
+        let mut bundle = Bundle { provider: Provider::Twemoji, size: 72, version: None, archive: archive.clone(), index: HashMap::new() };
+        for i in 0..archive.len() {
+            // by_index 只读取文件头，不解压
+            let Ok(mut file) = archive.by_index(i) else { continue };
+            let name = file.name().to_string();
+            if name == MANIFEST {
+                let mut manifest = String::new();
+                file.read_to_string(&mut manifest).map_err(|e| format!("读取内置图片包清单失败: {}", e))?;
+                for line in manifest.lines() {
+                    match line.split_once('=') {
+                        Some(("provider", v)) => bundle.provider = Provider::from_name(v).unwrap_or(bundle.provider),
+                        Some(("size", v)) => bundle.size = v.trim().parse().unwrap_or(bundle.size),
+                        Some(("version", v)) if !v.trim().is_empty() => bundle.version = Some(v.trim().to_string()),
+                        _ => {}
+                    }
+                }
+            } else if let Some(code) = naming::code_from_book_filename(&name) {
+                bundle.index.insert(code.to_lowercase(), i);
+            }
+        }
+        Ok(bundle)
+    }
+
+    /// 图片集、尺寸、版本都与清单一致时才使用
+    fn matches(&self, provider: Provider, size: u32, version: &str) -> bool {
+        self.provider == provider
+            && self.size == size
+            && self.version.as_deref().unwrap_or(self.provider.default_version()) == version
+    }
+
+    fn find(&self, provider: Provider, size: u32, version: &str, code: &str) -> Option<usize> {
+        if !self.matches(provider, size, version) {
+            return None;
+        }
+        self.index.get(code).or_else(|| self.index.get(&naming::code_without_fe0f(code))).copied()
+    }
+
+    fn read(&self, i: usize) -> Option<Vec<u8>> {
+        // ZipArchive 的 clone 只复制读取位置，中央目录共享
+        let mut archive = self.archive.clone();
+        let mut file = archive.by_index(i).ok()?;
+        let mut data = Vec::with_capacity(file.size() as usize);
+        match file.read_to_end(&mut data) {
+            Ok(_) => Some(data),
+            Err(e) => {
+                crate::log_info!("读取内置图片失败: {}: {}", file.name(), e);
+                None
+            }
+        }
+    }
+}
+
+fn bundle() -> Option<&'static Bundle> {
+    static BUNDLE: OnceLock<Option<Bundle>> = OnceLock::new();
+    BUNDLE
+        .get_or_init(|| match Bundle::parse(PACK) {
+            Ok(bundle) => Some(bundle),
+            Err(e) => {
+                crate::log_info!("{}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// 内置图片包中是否有该图片，图片集/尺寸/版本与内置包不一致时返回 false
+pub fn contains(provider: Provider, size: u32, version: &str, code: &str) -> bool {
+    bundle().and_then(|b| b.find(provider, size, version, code)).is_some()
+}
+
+/// 从内置图片包取图片，图片集/尺寸/版本与内置包不一致时返回 None
+pub fn get(provider: Provider, size: u32, version: &str, code: &str) -> Option<Vec<u8>> {
+    let bundle = bundle()?;
+    bundle.read(bundle.find(provider, size, version, code)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn pack(manifest: &str) -> &'static [u8] {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, data) in [(MANIFEST, manifest), ("1f600.png", "grinning"), ("2764.png", "heart"), ("readme.txt", "x")] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        Box::leak(writer.finish().unwrap().into_inner().into_boxed_slice())
+    }
+
+    #[test]
+    fn reads_images_on_demand() {
+        let bundle = Bundle::parse(pack("provider=openmoji\nsize=72\nversion=15.0\n")).unwrap();
+        assert_eq!(bundle.index.len(), 2);
+        let i = bundle.find(Provider::OpenMoji, 72, "15.0", "1f600").unwrap();
+        assert_eq!(bundle.read(i).unwrap(), b"grinning");
+        // 包中没有完全限定的文件名时按去掉 FE0F 的 code 查找
+        let i = bundle.find(Provider::OpenMoji, 72, "15.0", "2764-fe0f").unwrap();
+        assert_eq!(bundle.read(i).unwrap(), b"heart");
+        assert_eq!(bundle.find(Provider::OpenMoji, 72, "15.0", "1f601"), None);
+    }
+
+    #[test]
+    fn provider_size_and_version_must_match_the_manifest() {
+        let bundle = Bundle::parse(pack("provider=openmoji\nsize=72\nversion=15.0\n")).unwrap();
+        assert!(bundle.matches(Provider::OpenMoji, 72, "15.0"));
+        assert!(!bundle.matches(Provider::Twemoji, 72, "15.0"));
+        assert!(!bundle.matches(Provider::OpenMoji, 144, "15.0"));
+        assert!(!bundle.matches(Provider::OpenMoji, 72, "14.0"));
+        assert_eq!(bundle.find(Provider::OpenMoji, 72, "14.0", "1f600"), None);
+    }
+
+    #[test]
+    fn manifest_without_version_means_the_default_version() {
+        let bundle = Bundle::parse(pack("provider=twemoji\nsize=72\n")).unwrap();
+        assert!(bundle.matches(Provider::Twemoji, 72, Provider::Twemoji.default_version()));
+        assert!(!bundle.matches(Provider::Twemoji, 72, "0.0"));
+    }
+}
@@ -0,0 +1,159 @@
+//! 本地 emoji 图片缓存目录，以及图片包的导入/导出
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+use crate::naming;
+use crate::provider::Provider;
+
+/// 默认缓存目录：exe 所在目录下的 emoji_img
+pub fn default_cache_dir() -> PathBuf {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+        .unwrap_or_else(|| PathBuf::from("."));
+    exe_dir.join("emoji_img")
+}
+
+/// 指定图片集/尺寸/版本的图片目录
+///
+/// Twemoji 72x72 直接使用缓存根目录（兼容旧版 emoji_img 布局），
+/// 其他组合放在 `{provider}_{size}` 子目录下；指定了版本时为 `{provider}_{size}_{version}`。
+pub fn image_dir(cache_dir: &Path, provider: Provider, size: u32, version: Option<&str>) -> PathBuf {
+    match version {
+        Some(v) => cache_dir.join(format!("{}_{}_{}", provider.name(), size, v)),
+        None if provider == Provider::Twemoji && size == 72 => cache_dir.to_path_buf(),
+        None => cache_dir.join(format!("{}_{}", provider.name(), size)),
+    }
+}
+
+/// 在图片目录中查找 code 对应的图片
+///
+/// 先找完全一致的文件名，再找去掉 `-fe0f` 的变体（旧版缓存按上游文件名保存，大多不带 FE0F）。
+pub fn find_image(image_dir: &Path, code: &str) -> Option<PathBuf> {
+    let exact = image_dir.join(naming::book_filename(code));
+    if exact.exists() {
+        return Some(exact);
+    }
+    let stripped = naming::code_without_fe0f(code);
+    let fallback = image_dir.join(naming::book_filename(&stripped));
+    if stripped != code && fallback.exists() {
+        return Some(fallback);
+    }
+    None
+}
+
+/// 自制图片包的清单文件名
+const PACK_MANIFEST: &str = "emojipack.txt";
+
+/// 图片包（zip）导入结果
+#[derive(Debug)]
+pub struct ImportSummary {
+    pub provider: Provider,
+    pub size: u32,
+    pub version: Option<String>,
+    pub image_dir: PathBuf,
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+/// 把 emoji 图片包解压到缓存
+///
+/// 支持 Twemoji（`assets/72x72/1f600.png`）、Noto（`png/72/emoji_u1f600.png`）、
+/// OpenMoji（`1F600.png`）的官方发布包，以及 [`export_pack`] 导出的图片包。
+/// 文件名按 [`naming::code_from_source_filename`] 转换为缓存文件名。官方包中含多种尺寸时只导入 size 对应的目录。
+pub fn import_pack(
+    archive: &Path,
+    cache_dir: &Path,
+    provider: Provider,
+    size: u32,
+    version: Option<&str>,
+) -> Result<ImportSummary, String> {
+    let file = File::open(archive).map_err(|e| format!("打开图片包失败: {}", e))?;
+    let mut zip = ZipArchive::new(file).map_err(|e| format!("解析图片包失败: {}", e))?;
+
+    // 自制图片包以清单中的图片集/尺寸为准
+    let (mut provider, mut size, mut version, mut own_pack) = (provider, size, version.map(String::from), false);
+    if let Ok(mut manifest) = zip.by_name(PACK_MANIFEST) {
+        let mut content = String::new();
+        manifest.read_to_string(&mut content).map_err(|e| format!("读取图片包清单失败: {}", e))?;
+        for line in content.lines() {
+            match line.split_once('=') {
+                Some(("provider", v)) => provider = Provider::from_name(v).ok_or_else(|| format!("图片包清单中的未知图片集: {}", v))?,
+                Some(("size", v)) => size = v.trim().parse().map_err(|_| format!("图片包清单中的无效尺寸: {}", v))?,
+                Some(("version", v)) => {
+                    crate::provider::check_version(v.trim())?;
+                    version = Some(v.trim().to_string());
+                }
+                _ => {}
+            }
+        }
+        own_pack = true;
+    }
+
+    let names: Vec<String> = zip.file_names().map(|n| n.to_string()).collect();
+    let size_markers = [format!("{}x{}", size, size), size.to_string()];
+    let in_size_dir = |name: &str| name.split('/').any(|c| size_markers.iter().any(|m| m == c));
+    let filter_by_size = !own_pack && names.iter().any(|n| in_size_dir(n));
+
+    let image_dir = image_dir(cache_dir, provider, size, version.as_deref());
+    std::fs::create_dir_all(&image_dir).map_err(|e| format!("创建图片目录失败: {}", e))?;
+    let mut summary = ImportSummary { provider, size, version, image_dir: image_dir.clone(), imported: 0, skipped: 0 };
+    for name in names {
+        if filter_by_size && !in_size_dir(&name) {
+            continue;
+        }
+        let Some(code) = naming::code_from_source_filename(provider, &name) else {
+            continue;
+        };
+        let mut entry = zip.by_name(&name).map_err(|e| format!("读取图片包文件失败: {}", e))?;
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(|e| format!("读取图片包文件失败: {}", e))?;
+        let target = image_dir.join(naming::book_filename(&code));
+        if target.exists() && std::fs::metadata(&target).map(|m| m.len() == data.len() as u64).unwrap_or(false) {
+            summary.skipped += 1;
+            continue;
+        }
+        std::fs::write(&target, &data).map_err(|e| format!("写入图片失败: {}", e))?;
+        summary.imported += 1;
+    }
+    Ok(summary)
+}
+
+/// 把缓存中指定图片集/尺寸/版本的图片导出为可移植的图片包（zip），返回图片数量
+pub fn export_pack(
+    cache_dir: &Path,
+    provider: Provider,
+    size: u32,
+    version: Option<&str>,
+    output: &Path,
+) -> Result<usize, String> {
+    let image_dir = image_dir(cache_dir, provider, size, version);
+    let entries = std::fs::read_dir(&image_dir).map_err(|e| format!("读取图片目录失败: {}: {}", image_dir.display(), e))?;
+    let file = File::create(output).map_err(|e| format!("创建图片包失败: {}", e))?;
+    let mut writer = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    writer.start_file(PACK_MANIFEST, options).map_err(|e| e.to_string())?;
+    let mut manifest = format!("provider={}\nsize={}\n", provider.name(), size);
+    if let Some(v) = version {
+        manifest.push_str(&format!("version={}\n", v));
+    }
+    writer.write_all(manifest.as_bytes()).map_err(|e| e.to_string())?;
+    let mut count = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || path.extension().map(|e| e != "png").unwrap_or(true) {
+            continue;
+        }
+        let data = std::fs::read(&path).map_err(|e| format!("读取图片失败: {}", e))?;
+        writer
+            .start_file(entry.file_name().to_string_lossy(), options)
+            .map_err(|e| e.to_string())?;
+        writer.write_all(&data).map_err(|e| e.to_string())?;
+        count += 1;
+    }
+    writer.finish().map_err(|e| format!("图片包写入完成失败: {}", e))?;
+    Ok(count)
+}
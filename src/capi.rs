@@ -0,0 +1,398 @@
+//! 带版本号的 C ABI
+//!
+//! 调用方先用 `EpubEmojiX_options_init` 填充默认值，再修改需要的字段；
+//! `abi_version`/`struct_size` 用于以后在结构体末尾追加字段时保持兼容。
+//! 头文件见 `include/epubemojix.h`（`c-header` feature 构建时由 cbindgen 生成）。
+
+use std::ffi::{c_void, CStr, CString};
+use std::mem::{offset_of, size_of, size_of_val};
+use std::io::Cursor;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::addr_of;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::options_with_provider;
+use crate::detect::parse_unicode_version;
+use crate::filter::EmojiFilter;
+use crate::provider::{check_version, OptionsSource, Provider};
+use crate::replacer::{replace_emoji_in_epub_controlled, ProcessControl, Progress, ReplaceOptions};
+use crate::EmojiSourceMode;
+
+/// 当前 ABI 版本
+pub const EPUBEMOJIX_ABI_VERSION: u32 = 1;
+
+/// 成功
+pub const EPUBEMOJIX_OK: i32 = 0;
+/// 处理失败（读写、解析 epub 等）
+pub const EPUBEMOJIX_ERROR: i32 = 1;
+/// 配置文件或选项无效
+pub const EPUBEMOJIX_CONFIG_ERROR: i32 = 2;
+/// 已通过取消标志取消
+pub const EPUBEMOJIX_CANCELLED: i32 = 3;
+/// 参数无效（空指针、ABI 版本不支持等）
+pub const EPUBEMOJIX_INVALID_ARGUMENT: i32 = 4;
+
+/// 进度信息，每处理完一个 xhtml/html 文件回调一次
+#[repr(C)]
+pub struct EpubEmojiXProgress {
+    /// 刚处理完的文件（epub 内路径），只在回调期间有效
+    pub file: *const c_char,
+    pub files_done: u32,
+    pub files_total: u32,
+    /// 到目前为止替换的 emoji 数
+    pub emoji_count: u32,
+}
+
+/// 进度回调，user_data 为 [`EpubEmojiXOptions::user_data`]
+pub type EpubEmojiXProgressCallback = Option<extern "C" fn(progress: *const EpubEmojiXProgress, user_data: *mut c_void)>;
+
+/// 取消标志，可在其他线程调用 `EpubEmojiX_cancel_token_cancel`
+pub struct EpubEmojiXCancelToken {
+    flag: AtomicBool,
+}
+
+/// 处理选项，字符串均为 UTF-8，NULL 表示使用默认值
+#[repr(C)]
+pub struct EpubEmojiXOptions {
+    /// 调用方头文件中的 EPUBEMOJIX_ABI_VERSION，不能为 0
+    pub abi_version: u32,
+    /// 调用方的 sizeof(EpubEmojiXOptions)；超出该大小的字段使用默认值，多出的字段忽略
+    pub struct_size: u32,
+    /// 配置文件路径，先应用配置文件，再应用下面的字段
+    pub config_path: *const c_char,
+    /// 图片集：twemoji、twemoji-fork、noto、openmoji
+    pub provider: *const c_char,
+    /// 图片集版本（git 标签或分支）
+    pub provider_version: *const c_char,
+    /// 图片尺寸，0 为默认
+    pub size: u32,
+    /// 图片缓存目录
+    pub cache_dir: *const c_char,
+    /// 非 0 时离线，只使用缓存中已有的图片
+    pub offline: u32,
+    /// img 标签的 style 属性
+    pub img_style: *const c_char,
+    /// 非 0 时默认文本显示的符号（❤、© 等）不带 FE0F 也替换
+    pub replace_text_default: u32,
+    /// 非 0 时忽略 FE0E（文本显示）
+    pub ignore_text_selector: u32,
+    /// 只替换高于该版本的 emoji，如 "13.0"
+    pub min_version: *const c_char,
+    /// 包含规则，逗号分隔，格式同配置文件 include
+    pub include: *const c_char,
+    /// 排除规则，逗号分隔，格式同配置文件 exclude
+    pub exclude: *const c_char,
+    pub progress: EpubEmojiXProgressCallback,
+    pub user_data: *mut c_void,
+    /// 取消标志，可为 NULL
+    pub cancel: *const EpubEmojiXCancelToken,
+}
+
+/// 返回库的 ABI 版本
+#[export_name = "EpubEmojiX_abi_version"]
+pub extern "C" fn abi_version() -> u32 {
+    EPUBEMOJIX_ABI_VERSION
+}
+
+/// 用默认值填充 options
+///
+/// # Safety
+/// `options` 必须指向可写的 EpubEmojiXOptions。
+#[export_name = "EpubEmojiX_options_init"]
+pub unsafe extern "C" fn options_init(options: *mut EpubEmojiXOptions) {
+    if options.is_null() {
+        return;
+    }
+    unsafe { options.write(default_options()) };
+}
+
+fn default_options() -> EpubEmojiXOptions {
+    EpubEmojiXOptions {
+        abi_version: EPUBEMOJIX_ABI_VERSION,
+        struct_size: size_of::<EpubEmojiXOptions>() as u32,
+        config_path: std::ptr::null(),
+        provider: std::ptr::null(),
+        provider_version: std::ptr::null(),
+        size: 0,
+        cache_dir: std::ptr::null(),
+        offline: 0,
+        img_style: std::ptr::null(),
+        replace_text_default: 0,
+        ignore_text_selector: 0,
+        min_version: std::ptr::null(),
+        include: std::ptr::null(),
+        exclude: std::ptr::null(),
+        progress: None,
+        user_data: std::ptr::null_mut(),
+        cancel: std::ptr::null(),
+    }
+}
+
+/// 创建取消标志，用 `EpubEmojiX_cancel_token_free` 释放
+#[export_name = "EpubEmojiX_cancel_token_new"]
+pub extern "C" fn cancel_token_new() -> *mut EpubEmojiXCancelToken {
+    Box::into_raw(Box::new(EpubEmojiXCancelToken { flag: AtomicBool::new(false) }))
+}
+
+/// 请求取消，正在进行的处理会在下一个文件前停止并返回 EPUBEMOJIX_CANCELLED
+///
+/// # Safety
+/// `token` 必须为 `EpubEmojiX_cancel_token_new` 返回且尚未释放的指针。
+#[export_name = "EpubEmojiX_cancel_token_cancel"]
+pub unsafe extern "C" fn cancel_token_cancel(token: *const EpubEmojiXCancelToken) {
+    if let Some(token) = unsafe { token.as_ref() } {
+        token.flag.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 释放取消标志
+///
+/// # Safety
+/// `token` 必须为 `EpubEmojiX_cancel_token_new` 返回的指针，且不再被使用。
+#[export_name = "EpubEmojiX_cancel_token_free"]
+pub unsafe extern "C" fn cancel_token_free(token: *mut EpubEmojiXCancelToken) {
+    if !token.is_null() {
+        drop(unsafe { Box::from_raw(token) });
+    }
+}
+
+/// 处理 epub 文件
+///
+/// # Safety
+/// `input_path`、`output_path` 必须是有效的 C 字符串；`options` 为 NULL 或指向已初始化的 EpubEmojiXOptions。
+#[export_name = "EpubEmojiX_process_file"]
+pub unsafe extern "C" fn process_file(
+    input_path: *const c_char,
+    output_path: *const c_char,
+    options: *const EpubEmojiXOptions,
+) -> i32 {
+    let (Some(input), Some(output)) = (unsafe { c_str(input_path) }, unsafe { c_str(output_path) }) else {
+        return EPUBEMOJIX_INVALID_ARGUMENT;
+    };
+    let Ok(data) = std::fs::read(&input) else {
+        return EPUBEMOJIX_ERROR;
+    };
+    let result = unsafe { process(&data, options) };
+    match result {
+        Ok(out) => match std::fs::write(&output, out) {
+            Ok(()) => EPUBEMOJIX_OK,
+            Err(_) => EPUBEMOJIX_ERROR,
+        },
+        Err(code) => code,
+    }
+}
+
+/// 处理内存中的 epub，结果写入 *out_data/*out_len，用 `EpubEmojiX_free_buffer` 释放
+///
+/// # Safety
+/// `input` 必须指向 `input_len` 字节的可读内存；`out_data`、`out_len` 必须可写；
+/// `options` 为 NULL 或指向已初始化的 EpubEmojiXOptions。
+#[export_name = "EpubEmojiX_process_buffer"]
+pub unsafe extern "C" fn process_buffer(
+    input: *const u8,
+    input_len: usize,
+    out_data: *mut *mut u8,
+    out_len: *mut usize,
+    options: *const EpubEmojiXOptions,
+) -> i32 {
+    if input.is_null() || out_data.is_null() || out_len.is_null() {
+        return EPUBEMOJIX_INVALID_ARGUMENT;
+    }
+    let data = unsafe { std::slice::from_raw_parts(input, input_len) };
+    match unsafe { process(data, options) } {
+        Ok(out) => {
+            let out = out.into_boxed_slice();
+            unsafe {
+                *out_len = out.len();
+                *out_data = Box::into_raw(out) as *mut u8;
+            }
+            EPUBEMOJIX_OK
+        }
+        Err(code) => {
+            unsafe {
+                *out_data = std::ptr::null_mut();
+                *out_len = 0;
+            }
+            code
+        }
+    }
+}
+
+/// 释放 `EpubEmojiX_process_buffer` 返回的缓冲区
+///
+/// # Safety
+/// `data`、`len` 必须为 `EpubEmojiX_process_buffer` 返回的值，且只释放一次。
+#[export_name = "EpubEmojiX_free_buffer"]
+pub unsafe extern "C" fn free_buffer(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(data, len)) });
+    }
+}
+
+unsafe fn c_str(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned())
+}
+
+/// 读取调用方的选项：只读取 struct_size 范围内的完整字段，其余使用默认值
+///
+/// 旧版本头文件编译的程序结构体较短，新版本的较长，两者都能使用。
+unsafe fn read_options(ptr: *const EpubEmojiXOptions) -> Result<Option<EpubEmojiXOptions>, i32> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    let (abi_version, struct_size) =
+        unsafe { (addr_of!((*ptr).abi_version).read(), addr_of!((*ptr).struct_size).read() as usize) };
+    if abi_version == 0 || struct_size < offset_of!(EpubEmojiXOptions, struct_size) + size_of::<u32>() {
+        return Err(EPUBEMOJIX_INVALID_ARGUMENT);
+    }
+    let mut o = default_options();
+    macro_rules! read_fields {
+        ($($field:ident),*) => {$(
+            if offset_of!(EpubEmojiXOptions, $field) + size_of_val(&o.$field) <= struct_size {
+                o.$field = unsafe { addr_of!((*ptr).$field).read() };
+            }
+        )*};
+    }
+    read_fields!(
+        config_path,
+        provider,
+        provider_version,
+        size,
+        cache_dir,
+        offline,
+        img_style,
+        replace_text_default,
+        ignore_text_selector,
+        min_version,
+        include,
+        exclude,
+        progress,
+        user_data,
+        cancel
+    );
+    Ok(Some(o))
+}
+
+unsafe fn process(input: &[u8], options: *const EpubEmojiXOptions) -> Result<Vec<u8>, i32> {
+    let options = unsafe { read_options(options) }?;
+    let options = options.as_ref();
+    let opts = match options {
+        Some(o) => unsafe { to_replace_options(o) }.map_err(|e| {
+            crate::log_info!("选项无效: {}", e);
+            EPUBEMOJIX_CONFIG_ERROR
+        })?,
+        None => ReplaceOptions::default(),
+    };
+    let cancel = options.and_then(|o| unsafe { o.cancel.as_ref() }).map(|t| &t.flag);
+    let callback = options.and_then(|o| o.progress.map(|f| (f, o.user_data)));
+    let mut on_progress = |p: &Progress| {
+        if let Some((f, user_data)) = callback {
+            let file = CString::new(p.file).unwrap_or_default();
+            let progress = EpubEmojiXProgress {
+                file: file.as_ptr(),
+                files_done: p.files_done as u32,
+                files_total: p.files_total as u32,
+                emoji_count: p.emoji_count as u32,
+            };
+            f(&progress, user_data);
+        }
+    };
+    let mut control = ProcessControl { on_progress: Some(&mut on_progress), cancel };
+    let mut output = Cursor::new(Vec::new());
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        replace_emoji_in_epub_controlled(Cursor::new(input), &mut output, &opts, &mut OptionsSource(&opts), &mut control)
+    }));
+    match result {
+        Ok(Ok(_)) => Ok(output.into_inner()),
+        Ok(Err(_)) if cancel.map(|c| c.load(Ordering::Relaxed)).unwrap_or(false) => Err(EPUBEMOJIX_CANCELLED),
+        _ => Err(EPUBEMOJIX_ERROR),
+    }
+}
+
+unsafe fn to_replace_options(o: &EpubEmojiXOptions) -> Result<ReplaceOptions, String> {
+    let config = unsafe { c_str(o.config_path) }.filter(|s| !s.is_empty());
+    let provider = match unsafe { c_str(o.provider) } {
+        Some(name) => Some(Provider::from_name(&name).ok_or_else(|| format!("未知图片集: {}", name))?),
+        None => None,
+    };
+    let mut opts = options_with_provider(config.as_deref().map(std::path::Path::new), provider)?;
+    if let Some(version) = unsafe { c_str(o.provider_version) } {
+        check_version(&version)?;
+        opts.provider_version = Some(version);
+    }
+    if o.size != 0 {
+        opts.size = o.size;
+    }
+    if opts.emoji_font.is_none() && !opts.provider.sizes().contains(&opts.size) {
+        return Err(format!("{} 不支持尺寸 {}", opts.provider.name(), opts.size));
+    }
+    if let Some(dir) = unsafe { c_str(o.cache_dir) }.filter(|s| !s.is_empty()) {
+        opts.cache_dir = Some(dir.into());
+    }
+    if o.offline != 0 {
+        opts.source_mode = EmojiSourceMode::Local;
+    }
+    if let Some(style) = unsafe { c_str(o.img_style) } {
+        opts.img_style = style.replace('"', "'");
+    }
+    if o.replace_text_default != 0 {
+        opts.detection.text_default_requires_fe0f = false;
+    }
+    if o.ignore_text_selector != 0 {
+        opts.detection.honor_text_selector = false;
+    }
+    if let Some(v) = unsafe { c_str(o.min_version) } {
+        opts.detection.min_version = Some(parse_unicode_version(&v).ok_or_else(|| format!("无效版本号: {}", v))?);
+    }
+    if let Some(rules) = unsafe { c_str(o.include) } {
+        opts.detection.filter.include.extend(EmojiFilter::parse_rules(&rules)?);
+    }
+    if let Some(rules) = unsafe { c_str(o.exclude) } {
+        opts.detection.filter.exclude.extend(EmojiFilter::parse_rules(&rules)?);
+    }
+    opts.detection.filter.validate()?;
+    Ok(opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_beyond_struct_size_use_defaults() {
+        let mut o = default_options();
+        o.size = 72;
+        o.offline = 1;
+        o.user_data = std::ptr::dangling_mut();
+        o.struct_size = offset_of!(EpubEmojiXOptions, offline) as u32;
+        let read = unsafe { read_options(&o) }.unwrap().unwrap();
+        assert_eq!(read.size, 72);
+        assert_eq!(read.offline, 0);
+        assert!(read.user_data.is_null());
+    }
+
+    #[test]
+    fn newer_and_larger_struct_is_accepted() {
+        let mut o = default_options();
+        o.abi_version = EPUBEMOJIX_ABI_VERSION + 1;
+        o.struct_size += 16;
+        o.offline = 1;
+        let read = unsafe { read_options(&o) }.unwrap().unwrap();
+        assert_eq!(read.offline, 1);
+    }
+
+    #[test]
+    fn missing_header_is_rejected() {
+        let mut o = default_options();
+        o.struct_size = 4;
+        assert_eq!(unsafe { read_options(&o) }.err(), Some(EPUBEMOJIX_INVALID_ARGUMENT));
+        o.struct_size = size_of::<EpubEmojiXOptions>() as u32;
+        o.abi_version = 0;
+        assert_eq!(unsafe { read_options(&o) }.err(), Some(EPUBEMOJIX_INVALID_ARGUMENT));
+        assert!(unsafe { read_options(std::ptr::null()) }.unwrap().is_none());
+    }
+}
@@ -0,0 +1,218 @@
+//! XML 数字字符引用（`&#x1F600;`、`&#128512;`）解码
+//!
+//! 部分转换工具把 emoji 写成字符引用，甚至把一个 ZWJ 序列拆成多个引用。
+//! 解码后再按字素簇识别，非 emoji 的部分原样输出源文本，不改动其他引用。
+//! 只处理文本节点：标签（含属性值）、注释、CDATA 与处理指令原样保留。
+
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// 解码后的文本，记录每个字符在源文本中的位置
+pub struct DecodedText<'a> {
+    source: &'a str,
+    text: String,
+    /// 解码文本字节偏移 -> 源文本字节偏移（只在字符边界有意义），长度为 text.len() + 1
+    offsets: Vec<usize>,
+    /// 解码文本中依次排列的各段：(字节范围, 是否为标记)
+    segments: Vec<(Range<usize>, bool)>,
+}
+
+/// 一段内容：文本节点中的一个字素簇，或一整段标记
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Piece<'t, 'a> {
+    /// 解码后的文本，标记时与源文本相同
+    pub text: &'t str,
+    /// 对应的源文本
+    pub source: &'a str,
+    /// 是否为标签、注释等标记
+    pub markup: bool,
+}
+
+impl<'a> DecodedText<'a> {
+    /// 解码文本节点中的数字字符引用；`&#60;` 等标记字符不解码，避免破坏标记
+    pub fn new(source: &'a str) -> Self {
+        let mut text = String::with_capacity(source.len());
+        let mut offsets = Vec::with_capacity(source.len() + 1);
+        let mut segments = Vec::new();
+        let mut text_start = 0;
+        let mut pos = 0;
+        while pos < source.len() {
+            let rest = &source[pos..];
+            if let Some(len) = markup_len(rest) {
+                if text.len() > text_start {
+                    segments.push((text_start..text.len(), false));
+                }
+                let start = text.len();
+                text.push_str(&rest[..len]);
+                offsets.extend(pos..pos + len);
+                segments.push((start..text.len(), true));
+                text_start = text.len();
+                pos += len;
+                continue;
+            }
+            let (ch, len) = match parse_char_ref(rest) {
+                Some((c, len)) if !matches!(c, '<' | '>' | '&' | '"' | '\'') => (c, len),
+                _ => {
+                    let c = rest.chars().next().unwrap();
+                    (c, c.len_utf8())
+                }
+            };
+            for _ in 0..ch.len_utf8() {
+                offsets.push(pos);
+            }
+            text.push(ch);
+            pos += len;
+        }
+        if text.len() > text_start {
+            segments.push((text_start..text.len(), false));
+        }
+        offsets.push(source.len());
+        DecodedText { source, text, offsets, segments }
+    }
+
+    /// 源文本
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// 解码后的文本（标记部分为原文）
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// 解码文本中的字节范围对应的源文本字节范围
+    pub fn source_range(&self, range: Range<usize>) -> Range<usize> {
+        self.offsets[range.start]..self.offsets[range.end]
+    }
+
+    /// 文本节点在解码文本中的字节范围
+    pub fn text_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.segments.iter().filter(|(_, markup)| !markup).map(|(range, _)| range.clone())
+    }
+
+    /// 依次返回文本节点中的字素簇与整段标记，拼接各段的 source 即为源文本
+    pub fn pieces(&self) -> impl Iterator<Item = Piece<'_, 'a>> + '_ {
+        self.segments.iter().flat_map(move |(range, markup)| {
+            let text = &self.text[range.clone()];
+            let graphemes: Box<dyn Iterator<Item = (usize, &str)>> = if *markup {
+                Box::new(std::iter::once((0, text)))
+            } else {
+                Box::new(text.grapheme_indices(true))
+            };
+            graphemes.map(move |(i, g)| {
+                let start = range.start + i;
+                Piece { text: g, source: &self.source[self.source_range(start..start + g.len())], markup: *markup }
+            })
+        })
+    }
+}
+
+/// 开头为标签、注释、CDATA 或处理指令时返回其长度
+///
+/// 同 HTML 的规则，`<` 后不是字母、`/`、`!`、`?` 时按文本处理，纯文本中的 `a < b` 不受影响。
+fn markup_len(s: &str) -> Option<usize> {
+    let next = s.strip_prefix('<')?.chars().next()?;
+    if !(next.is_ascii_alphabetic() || matches!(next, '/' | '!' | '?')) {
+        return None;
+    }
+    let closed = |end: &str| s.find(end).map(|i| i + end.len()).unwrap_or(s.len());
+    if s.starts_with("<!--") {
+        return Some(closed("-->"));
+    }
+    if s.starts_with("<![CDATA[") {
+        return Some(closed("]]>"));
+    }
+    // 属性值中可以出现 `>`
+    let mut quote = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '>') => return Some(i + 1),
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    Some(s.len())
+}
+
+/// 解析开头的 `&#x...;` 或 `&#...;`，返回字符与引用长度
+fn parse_char_ref(s: &str) -> Option<(char, usize)> {
+    let body = s.strip_prefix("&#")?;
+    let end = body.find(';')?;
+    let digits = &body[..end];
+    let value = match digits.strip_prefix('x').or_else(|| digits.strip_prefix('X')) {
+        Some(hex) if !hex.is_empty() && hex.len() <= 8 => u32::from_str_radix(hex, 16).ok()?,
+        None if !digits.is_empty() && digits.len() <= 10 => digits.parse().ok()?,
+        _ => return None,
+    };
+    Some((char::from_u32(value)?, end + 3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(source: &str) -> Vec<(&str, bool)> {
+        DecodedText::new(source).pieces().map(|p| (p.source, p.markup)).collect()
+    }
+
+    #[test]
+    fn decodes_hex_and_decimal_references() {
+        let decoded = DecodedText::new("a&#x1F600;b&#128512;");
+        assert_eq!(decoded.text(), "a😀b😀");
+        let pieces: Vec<_> = decoded.pieces().map(|p| (p.text, p.source)).collect();
+        assert_eq!(pieces, [("a", "a"), ("😀", "&#x1F600;"), ("b", "b"), ("😀", "&#128512;")]);
+    }
+
+    #[test]
+    fn zwj_sequence_split_across_references_is_one_grapheme() {
+        let decoded = DecodedText::new("&#x1F469;&#x200D;&#x1F4BB;!");
+        let pieces: Vec<_> = decoded.pieces().map(|p| (p.text, p.source)).collect();
+        assert_eq!(pieces, [("👩\u{200d}💻", "&#x1F469;&#x200D;&#x1F4BB;"), ("!", "!")]);
+    }
+
+    #[test]
+    fn markup_characters_stay_encoded() {
+        let source = "&#60;b&#62; &#38; &#x22; &#39;";
+        let decoded = DecodedText::new(source);
+        assert_eq!(decoded.text(), source);
+    }
+
+    #[test]
+    fn invalid_and_out_of_range_references_are_kept() {
+        for source in ["&#xD800;", "&#x110000;", "&#99999999999;", "&#x;", "&#;", "&#xZZ;", "&#x1F600"] {
+            assert_eq!(DecodedText::new(source).text(), source, "{}", source);
+        }
+    }
+
+    #[test]
+    fn source_range_maps_back_to_references() {
+        let decoded = DecodedText::new("x&#x1F600;y");
+        assert_eq!(decoded.source_range(1..5), 1..10);
+        assert_eq!(decoded.source_range(5..6), 10..11);
+        assert_eq!(decoded.source_range(0..decoded.text().len()), 0..11);
+    }
+
+    #[test]
+    fn attributes_and_comments_are_not_decoded() {
+        let source = r#"<p title="&#x1F600; > hi">&#x1F600;</p><!-- &#128512; --><img alt='&#128512;'/>"#;
+        let decoded = DecodedText::new(source);
+        assert_eq!(
+            texts(source),
+            [
+                (r#"<p title="&#x1F600; > hi">"#, true),
+                ("&#x1F600;", false),
+                ("</p>", true),
+                ("<!-- &#128512; -->", true),
+                ("<img alt='&#128512;'/>", true),
+            ]
+        );
+        assert_eq!(decoded.text_ranges().count(), 1);
+    }
+
+    #[test]
+    fn less_than_in_plain_text_is_text() {
+        assert_eq!(texts("a < b"), [("a", false), (" ", false), ("<", false), (" ", false), ("b", false)]);
+    }
+}
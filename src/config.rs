@@ -0,0 +1,156 @@
+//! 配置文件
+//!
+//! 每行一个 `key = value`，`#` 开头为注释；include/exclude/keep_text 可多次出现或用逗号分隔。
+//!
+//! ```text
+//! provider = noto
+//! size = 128
+//! provider_version = 15.1.0
+//! cache_dir = D:\emoji_cache
+//! offline = true
+//! include = group:Flags
+//! exclude = ❤️, :broken_heart:, subgroup:heart
+//! keep_text = ©
+//! replace_text_default = false
+//! ignore_text_selector = false
+//! min_version = 11.0
+//! font = C:\Windows\Fonts\seguiemj.ttf
+//! embedded_fonts = true
+//! font_require_color = true
+//! emoji_test = emoji-test.txt
+//! emoji_font = C:\Windows\Fonts\seguiemj.ttf
+//! img_style = height:1em;vertical-align:middle
+//! ```
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::detect::parse_unicode_version;
+use crate::emoji_data::EmojiTestData;
+use crate::filter::EmojiFilter;
+use crate::naming::strip_selectors;
+use crate::provider::{check_version, Provider};
+use crate::render::EmojiFont;
+use crate::replacer::ReplaceOptions;
+use crate::EmojiSourceMode;
+
+/// 读取配置文件并应用到 opts，相对路径以配置文件所在目录为基准
+pub fn apply_config_file(path: &Path, opts: &mut ReplaceOptions) -> Result<(), String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("读取配置文件失败: {}: {}", path.display(), e))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    apply_config(&content, base_dir, opts)
+}
+
+/// 按配置文件与调用方指定的图片集生成选项
+///
+/// provider 覆盖配置中的 provider；尺寸、版本只有配置中没有设置时才取该图片集的默认值。
+pub fn options_with_provider(config: Option<&Path>, provider: Option<Provider>) -> Result<ReplaceOptions, String> {
+    let mut opts = ReplaceOptions::default();
+    if let Some(provider) = provider {
+        opts.provider = provider;
+        opts.size = provider.default_size();
+    }
+    if let Some(path) = config {
+        apply_config_file(path, &mut opts)?;
+    }
+    if let Some(provider) = provider {
+        opts.provider = provider;
+    }
+    Ok(opts)
+}
+
+/// 应用配置文本
+///
+/// 最后检查包含/排除规则，子分组规则需要同一配置中的 emoji_test。
+pub fn apply_config(content: &str, base_dir: &Path, opts: &mut ReplaceOptions) -> Result<(), String> {
+    let mut keep_text_set = false;
+    for (lineno, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("配置第 {} 行格式错误: {}", lineno + 1, line))?;
+        let (key, value) = (key.trim(), value.trim());
+        match key {
+            "provider" => opts.provider = Provider::from_name(value).ok_or_else(|| format!("未知图片集: {}", value))?,
+            "size" => opts.size = value.parse().map_err(|_| format!("无效尺寸: {}", value))?,
+            "provider_version" => {
+                check_version(value)?;
+                opts.provider_version = Some(value.to_string());
+            }
+            "cache_dir" => opts.cache_dir = Some(base_dir.join(value)),
+            "offline" => {
+                opts.source_mode = if parse_bool(value)? { EmojiSourceMode::Local } else { EmojiSourceMode::Online }
+            }
+            "include" => opts.detection.filter.include.extend(EmojiFilter::parse_rules(value)?),
+            "exclude" => opts.detection.filter.exclude.extend(EmojiFilter::parse_rules(value)?),
+            "keep_text" => {
+                if !keep_text_set {
+                    opts.detection.keep_text.clear();
+                    keep_text_set = true;
+                }
+                opts.detection.keep_text.extend(
+                    value
+                        .split(',')
+                        .map(|s| strip_selectors(s.trim()))
+                        .filter(|s| !s.is_empty()),
+                );
+            }
+            "replace_text_default" => opts.detection.text_default_requires_fe0f = !parse_bool(value)?,
+            "ignore_text_selector" => opts.detection.honor_text_selector = !parse_bool(value)?,
+            "min_version" => {
+                opts.detection.min_version = Some(parse_unicode_version(value).ok_or_else(|| format!("无效版本号: {}", value))?)
+            }
+            "font" => {
+                opts.detection.font_coverage.add_font_file(&base_dir.join(value))?;
+            }
+            "embedded_fonts" => opts.detection.embedded_fonts = parse_bool(value)?,
+            "font_require_color" => opts.detection.font_coverage.require_color = parse_bool(value)?,
+            "emoji_test" => opts.detection.filter.data = Some(Arc::new(EmojiTestData::load(&base_dir.join(value))?)),
+            "img_style" => opts.img_style = value.replace('"', "'"),
+            "emoji_font" => opts.emoji_font = Some(EmojiFont::load(&base_dir.join(value))?),
+            _ => return Err(format!("配置第 {} 行未知选项: {}", lineno + 1, key)),
+        }
+    }
+    opts.detection.filter.validate()
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "1" | "on" => Ok(true),
+        "false" | "no" | "0" | "off" => Ok(false),
+        _ => Err(format!("无效布尔值: {}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provider_override_keeps_size_and_version_from_config() {
+        let dir = std::env::temp_dir().join(format!("epubemojix_provider_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (with_size, without_size) = (dir.join("size.cfg"), dir.join("plain.cfg"));
+        std::fs::write(&with_size, "provider = twemoji\nsize = 512\nprovider_version = 15.0.0\n").unwrap();
+        std::fs::write(&without_size, "provider = twemoji\n").unwrap();
+        let kept = options_with_provider(Some(&with_size), Some(Provider::Noto));
+        let defaulted = options_with_provider(Some(&without_size), Some(Provider::Noto));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let kept = kept.unwrap();
+        assert_eq!((kept.provider, kept.size, kept.provider_version.as_deref()), (Provider::Noto, 512, Some("15.0.0")));
+        let defaulted = defaulted.unwrap();
+        assert_eq!((defaulted.provider, defaulted.size, defaulted.provider_version), (Provider::Noto, Provider::Noto.default_size(), None));
+    }
+
+    #[test]
+    fn subgroup_rule_needs_emoji_test_in_config() {
+        let mut opts = ReplaceOptions::default();
+        let err = apply_config("exclude = subgroup:heart", Path::new(""), &mut opts).unwrap_err();
+        assert!(err.contains("subgroup:heart"), "{}", err);
+        let mut opts = ReplaceOptions::default();
+        assert!(apply_config("exclude = group:Flags", Path::new(""), &mut opts).is_ok());
+    }
+}
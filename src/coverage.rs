@@ -0,0 +1,268 @@
+//! 字体覆盖检测：只替换指定字体中没有字形的 emoji
+//!
+//! 单个字符查 cmap；多码点序列（ZWJ、国旗、键帽、肤色）还需要 GSUB 中有对应的连字。
+//! 没有 GSUB 的字体（如使用 morx 的 Apple Color Emoji）中，多码点序列一律视为未覆盖。
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use ttf_parser::gsub::SubstitutionSubtable;
+use ttf_parser::{Face, GlyphId};
+
+/// 一组字体的覆盖范围，任一字体能显示即视为覆盖
+#[derive(Clone, Default)]
+pub struct FontCoverage {
+    fonts: Vec<Arc<FontData>>,
+    /// 要求字形是彩色的（COLR/CBDT/sbix/SVG），只有黑白轮廓的字形视为未覆盖
+    pub require_color: bool,
+    cache: Arc<Mutex<HashMap<String, bool>>>,
+}
+
+struct FontData {
+    name: String,
+    data: Arc<Vec<u8>>,
+    index: u32,
+}
+
+impl std::fmt::Debug for FontCoverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FontCoverage")
+            .field("fonts", &self.fonts.iter().map(|f| format!("{}#{}", f.name, f.index)).collect::<Vec<_>>())
+            .field("require_color", &self.require_color)
+            .finish()
+    }
+}
+
+impl FontCoverage {
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty()
+    }
+
+    /// 加载 TTF/OTF/TTC 字体文件，返回加载的字体数（TTC 中每个字体分别计算）
+    pub fn add_font_file(&mut self, path: &Path) -> Result<usize, String> {
+        let data = std::fs::read(path).map_err(|e| format!("读取字体失败: {}: {}", path.display(), e))?;
+        self.add_font_data(&path.display().to_string(), data)
+    }
+
+    pub fn add_font_data(&mut self, name: &str, data: Vec<u8>) -> Result<usize, String> {
+        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        let data = Arc::new(data);
+        let mut added = 0;
+        for index in 0..count {
+            if Face::parse(&data, index).is_ok() {
+                self.fonts.push(Arc::new(FontData { name: name.to_string(), data: data.clone(), index }));
+                added += 1;
+            }
+        }
+        if added == 0 {
+            return Err(format!("无法解析字体（仅支持 TTF/OTF/TTC）: {}", name));
+        }
+        // 克隆出的 FontCoverage 共享缓存，字体变化后换用新的缓存
+        self.cache = Arc::new(Mutex::new(HashMap::new()));
+        Ok(added)
+    }
+
+    /// 字素簇是否能被这组字体显示
+    pub fn covers(&self, g: &str) -> bool {
+        if let Some(&hit) = self.cache.lock().unwrap().get(g) {
+            return hit;
+        }
+        let hit = self.fonts.iter().any(|font| {
+            Face::parse(&font.data, font.index)
+                .map(|face| face_covers(&face, g, self.require_color))
+                .unwrap_or(false)
+        });
+        self.cache.lock().unwrap().insert(g.to_string(), hit);
+        hit
+    }
+}
+
+fn face_covers(face: &Face, g: &str, require_color: bool) -> bool {
+    match sequence_glyph(face, g) {
+        Some(glyph) => !require_color || is_color(face, glyph),
+        None => false,
+    }
+}
+
+/// 字素簇在字体中对应的字形：单个字符查 cmap，多码点序列查 GSUB 连字
+pub(crate) fn sequence_glyph(face: &Face, g: &str) -> Option<GlyphId> {
+    let mut pairs = Vec::new();
+    for c in g.chars().filter(|c| *c != '\u{fe0e}') {
+        match face.glyph_index(c) {
+            Some(gid) => pairs.push((c, gid)),
+            // 字体通常不为 FE0F 单独建字形
+            None if c == '\u{fe0f}' => {}
+            None => return None,
+        }
+    }
+    let glyphs: Vec<GlyphId> = pairs.iter().map(|(_, gid)| *gid).collect();
+    let without_vs: Vec<GlyphId> = pairs.iter().filter(|(c, _)| *c != '\u{fe0f}').map(|(_, gid)| *gid).collect();
+    match (glyphs.as_slice(), without_vs.as_slice()) {
+        ([], _) => None,
+        ([single], _) => Some(*single),
+        // 带 FE0F 的单个字符：去掉 FE0F 后只剩一个字形
+        (_, [single]) => Some(*single),
+        _ => find_ligature(face, &glyphs).or_else(|| find_ligature(face, &without_vs)),
+    }
+}
+
+/// 在 GSUB 连字替换中查找 glyphs 对应的连字字形
+fn find_ligature(face: &Face, glyphs: &[GlyphId]) -> Option<GlyphId> {
+    let (first, rest) = glyphs.split_first()?;
+    let gsub = face.tables().gsub?;
+    for lookup in gsub.lookups {
+        for subtable in lookup.subtables.into_iter::<SubstitutionSubtable>() {
+            let SubstitutionSubtable::Ligature(lig) = subtable else { continue };
+            let Some(index) = lig.coverage.get(*first) else { continue };
+            let Some(set) = lig.ligature_sets.get(index) else { continue };
+            for ligature in set {
+                if ligature.components.len() as usize == rest.len()
+                    && ligature.components.into_iter().zip(rest).all(|(a, b)| a == *b)
+                {
+                    return Some(ligature.glyph);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn is_color(face: &Face, glyph: GlyphId) -> bool {
+    face.is_color_glyph(glyph) || face.glyph_raster_image(glyph, 128).is_some() || face.glyph_svg_image(glyph).is_some()
+}
+
+/// 从 css 文本中提取 @font-face 的 src url
+pub fn font_face_urls(css: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut rest = css;
+    while let Some(start) = rest.find("@font-face") {
+        let block = &rest[start..];
+        let end = block.find('}').unwrap_or(block.len());
+        let mut body = &block[..end];
+        while let Some(u) = body.find("url(") {
+            let after = &body[u + 4..];
+            let Some(close) = after.find(')') else { break };
+            let url = after[..close].trim().trim_matches(|c| c == '"' || c == '\'');
+            if !url.is_empty() {
+                urls.push(url.to_string());
+            }
+            body = &after[close..];
+        }
+        rest = &block[end..];
+    }
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// 测试字体：😀 ❤ 👩 💻 🇺 🇸 1 ZWJ U+20E3 各有字形，GSUB 中有 👩‍💻 与 1⃣ 的连字，
+    /// COLR 中 😀 与 👩‍💻 为彩色字形
+    fn test_font() -> Vec<u8> {
+        let mut head = vec![0u8; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        let mut hhea = vec![0u8; 36];
+        hhea[..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        let mut maxp = 0x0000_5000u32.to_be_bytes().to_vec();
+        maxp.extend(u16s(&[12]));
+
+        let groups: [(u32, u32); 9] =
+            [(0x31, 9), (0x200d, 3), (0x20e3, 10), (0x2764, 2), (0x1f1f8, 8), (0x1f1fa, 7), (0x1f469, 4), (0x1f4bb, 5), (0x1f600, 1)];
+        let mut cmap = u16s(&[0, 1, 3, 10]);
+        cmap.extend(12u32.to_be_bytes());
+        cmap.extend(u16s(&[12, 0]));
+        cmap.extend((16 + 12 * groups.len() as u32).to_be_bytes());
+        cmap.extend(0u32.to_be_bytes());
+        cmap.extend((groups.len() as u32).to_be_bytes());
+        for (c, gid) in groups {
+            for v in [c, c, gid] {
+                cmap.extend(v.to_be_bytes());
+            }
+        }
+
+        // 头部、空的 ScriptList/FeatureList、一个连字 lookup
+        let mut gsub = u16s(&[1, 0, 10, 12, 14, 0, 0, 1, 4, 4, 0, 1, 8]);
+        gsub.extend(u16s(&[1, 10, 2, 18, 30, 1, 2, 4, 9, 1, 4, 6, 3, 3, 5, 1, 4, 11, 2, 10]));
+
+        let mut colr = u16s(&[0, 2]);
+        colr.extend(14u32.to_be_bytes());
+        colr.extend(26u32.to_be_bytes());
+        colr.extend(u16s(&[1, 1, 0, 1, 6, 0, 1, 1, 0]));
+        let mut cpal = u16s(&[0, 1, 1, 1]);
+        cpal.extend(14u32.to_be_bytes());
+        cpal.extend(u16s(&[0]));
+        cpal.extend([0, 0, 255, 255]);
+
+        let tables: [(&[u8; 4], Vec<u8>); 7] =
+            [(b"COLR", colr), (b"CPAL", cpal), (b"GSUB", gsub), (b"cmap", cmap), (b"head", head), (b"hhea", hhea), (b"maxp", maxp)];
+        let mut font = 0x0001_0000u32.to_be_bytes().to_vec();
+        font.extend(u16s(&[tables.len() as u16, 0, 0, 0]));
+        let mut offset = 12 + 16 * tables.len();
+        let mut data = Vec::new();
+        for (tag, table) in &tables {
+            font.extend(*tag);
+            font.extend(0u32.to_be_bytes());
+            font.extend((offset as u32).to_be_bytes());
+            font.extend((table.len() as u32).to_be_bytes());
+            data.extend(table);
+            while data.len() % 4 != 0 {
+                data.push(0);
+            }
+            offset = 12 + 16 * tables.len() + data.len();
+        }
+        font.extend(data);
+        font
+    }
+
+    fn coverage(require_color: bool) -> FontCoverage {
+        let mut coverage = FontCoverage { require_color, ..FontCoverage::default() };
+        assert_eq!(coverage.add_font_data("test", test_font()), Ok(1));
+        coverage
+    }
+
+    #[test]
+    fn single_characters_use_cmap() {
+        let coverage = coverage(false);
+        assert!(coverage.covers("😀"));
+        assert!(coverage.covers("❤\u{fe0f}"));
+        assert!(coverage.covers("❤\u{fe0e}"));
+        assert!(!coverage.covers("🦖"));
+    }
+
+    #[test]
+    fn sequences_need_a_gsub_ligature() {
+        let coverage = coverage(false);
+        assert!(coverage.covers("👩\u{200d}💻"));
+        assert!(coverage.covers("1\u{20e3}"));
+        assert!(coverage.covers("1\u{fe0f}\u{20e3}"));
+        // 两个区域指示符都在 cmap 中，但没有国旗连字
+        assert!(!coverage.covers("🇺🇸"));
+        assert!(!coverage.covers("💻\u{200d}👩"));
+    }
+
+    #[test]
+    fn require_color_rejects_outline_glyphs() {
+        let coverage = coverage(true);
+        assert!(coverage.covers("😀"));
+        assert!(coverage.covers("👩\u{200d}💻"));
+        assert!(!coverage.covers("❤\u{fe0f}"));
+        assert!(!coverage.covers("1\u{20e3}"));
+    }
+
+    #[test]
+    fn invalid_font_data_is_rejected() {
+        assert!(FontCoverage::default().add_font_data("bad", vec![0; 16]).is_err());
+    }
+
+    #[test]
+    fn finds_font_face_urls() {
+        let css = r#"p { x: url(a.png) } @font-face { font-family: A; src: url("../Fonts/a.ttf") format("truetype"), url('b.otf'); }"#;
+        assert_eq!(font_face_urls(css), ["../Fonts/a.ttf", "b.otf"]);
+    }
+}
@@ -0,0 +1,223 @@
+//! emoji 识别策略
+//!
+//! 中日文小说常把 ©、®、™、♀ 等当作普通排版符号使用，这些字符默认按文本显示，
+//! 只有显式带 FE0F 时才是 emoji。
+
+use emojis::UnicodeVersion;
+
+use crate::coverage::FontCoverage;
+use crate::filter::EmojiFilter;
+use crate::naming::{code_of, code_to_str, strip_selectors};
+
+/// 默认保留为文本的符号
+pub const DEFAULT_KEEP_TEXT: &[&str] = &["©", "®", "™", "‼", "⁉", "♀", "♂", "〰", "〽"];
+
+/// 识别策略
+#[derive(Clone, Debug)]
+pub struct DetectionPolicy {
+    /// 带 U+FE0E（文本显示）的字符不替换
+    pub honor_text_selector: bool,
+    /// 默认文本显示的字符（如 ❤、©、键帽）只有带 FE0F 时才替换
+    pub text_default_requires_fe0f: bool,
+    /// 始终保留为文本的符号（不含变体选择符）
+    pub keep_text: Vec<String>,
+    /// 包含/排除规则
+    pub filter: EmojiFilter,
+    /// 不高于该版本的 emoji 阅读器系统字体一般能显示，保持文本，只替换更新的
+    pub min_version: Option<UnicodeVersion>,
+    /// 阅读器字体，能显示的 emoji 保持文本；为空时不检查
+    pub font_coverage: FontCoverage,
+    /// 同时使用 epub 内 @font-face 引用的字体判断覆盖
+    pub embedded_fonts: bool,
+}
+
+impl Default for DetectionPolicy {
+    fn default() -> Self {
+        DetectionPolicy {
+            honor_text_selector: true,
+            text_default_requires_fe0f: true,
+            keep_text: DEFAULT_KEEP_TEXT.iter().map(|s| s.to_string()).collect(),
+            filter: EmojiFilter::default(),
+            min_version: None,
+            font_coverage: FontCoverage::default(),
+            embedded_fonts: false,
+        }
+    }
+}
+
+impl DetectionPolicy {
+    /// 旧版行为：emojis 库能识别的都替换
+    pub fn replace_all() -> Self {
+        DetectionPolicy {
+            honor_text_selector: false,
+            text_default_requires_fe0f: false,
+            keep_text: Vec::new(),
+            filter: EmojiFilter::default(),
+            min_version: None,
+            font_coverage: FontCoverage::default(),
+            embedded_fonts: false,
+        }
+    }
+
+    /// 从文件读取保留符号列表，每行一个，`#` 开头为注释
+    pub fn load_keep_text(path: &std::path::Path) -> Result<Vec<String>, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("读取保留符号列表失败: {}", e))?;
+        Ok(content
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(strip_selectors)
+            .collect())
+    }
+}
+
+/// 判断字素簇是否按策略替换为图片
+///
+/// 除 emojis 库外，加载了 emoji-test 数据（`filter.data`）时其中的完全限定条目同样识别，
+/// 用于识别 emojis 库尚未收录的新 emoji。
+pub fn is_emoji_grapheme(g: &str, policy: &DetectionPolicy) -> bool {
+    let has_fe0e = g.contains('\u{fe0e}');
+    if has_fe0e && policy.honor_text_selector {
+        return false;
+    }
+    let base = strip_selectors(g);
+    let (fully_qualified, version) = match emojis::get(g).or_else(|| emojis::get(&base)) {
+        Some(emoji) => (emoji.as_str().to_string(), Some(emoji.unicode_version())),
+        None => {
+            let Some(entry) = policy.filter.data.as_deref().and_then(|d| d.fully_qualified(&code_of(&base))) else {
+                return false;
+            };
+            (code_to_str(&entry.code).unwrap_or_default(), entry.version.map(|(major, minor)| UnicodeVersion::new(major, minor)))
+        }
+    };
+    if policy.keep_text.contains(&base) {
+        return false;
+    }
+    if let (Some(min), Some(version)) = (policy.min_version, version) {
+        if version <= min {
+            return false;
+        }
+    }
+    if !policy.font_coverage.is_empty() && policy.font_coverage.covers(g) {
+        return false;
+    }
+    if policy.text_default_requires_fe0f && !g.contains('\u{fe0f}') && is_text_default(&base, &fully_qualified) {
+        return false;
+    }
+    policy.filter.allows(g)
+}
+
+/// 单个字符（或键帽）且完全限定形式需要 FE0F，即默认文本显示
+fn is_text_default(base: &str, fully_qualified: &str) -> bool {
+    let mut chars = base.chars().filter(|c| *c != '\u{20e3}');
+    let single = chars.next().is_some() && chars.next().is_none();
+    single && fully_qualified.contains('\u{fe0f}')
+}
+
+/// 解析 Unicode 版本，如 `14`、`13.1`
+pub fn parse_unicode_version(s: &str) -> Option<UnicodeVersion> {
+    let mut parts = s.trim().splitn(2, '.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = match parts.next() {
+        Some(m) => m.parse().ok()?,
+        None => 0,
+    };
+    Some(UnicodeVersion::new(major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detects(g: &str, policy: &DetectionPolicy) -> bool {
+        is_emoji_grapheme(g, policy)
+    }
+
+    #[test]
+    fn text_selector_forces_text() {
+        let policy = DetectionPolicy::default();
+        assert!(!detects("😀\u{fe0e}", &policy));
+        assert!(!detects("❤\u{fe0e}", &policy));
+        let policy = DetectionPolicy { honor_text_selector: false, ..DetectionPolicy::default() };
+        assert!(detects("😀\u{fe0e}", &policy));
+    }
+
+    #[test]
+    fn text_default_symbols_need_fe0f() {
+        let policy = DetectionPolicy::default();
+        assert!(!detects("❤", &policy));
+        assert!(detects("❤\u{fe0f}", &policy));
+        assert!(detects("😀", &policy));
+        let policy = DetectionPolicy { text_default_requires_fe0f: false, ..DetectionPolicy::default() };
+        assert!(detects("❤", &policy));
+    }
+
+    #[test]
+    fn keycaps_follow_the_text_default_rule() {
+        let policy = DetectionPolicy::default();
+        assert!(!detects("1\u{20e3}", &policy));
+        assert!(detects("1\u{fe0f}\u{20e3}", &policy));
+        assert!(detects("#\u{fe0f}\u{20e3}", &policy));
+        let policy = DetectionPolicy { text_default_requires_fe0f: false, ..DetectionPolicy::default() };
+        assert!(detects("1\u{20e3}", &policy));
+    }
+
+    #[test]
+    fn keep_text_defaults_stay_text_even_with_fe0f() {
+        let policy = DetectionPolicy::default();
+        for symbol in DEFAULT_KEEP_TEXT {
+            assert!(!detects(&format!("{}\u{fe0f}", symbol), &policy), "{}", symbol);
+        }
+        let policy = DetectionPolicy { keep_text: vec!["😀".to_string()], ..DetectionPolicy::default() };
+        assert!(!detects("😀", &policy));
+        assert!(detects("©\u{fe0f}", &policy));
+    }
+
+    #[test]
+    fn replace_all_replaces_text_presentation() {
+        let policy = DetectionPolicy::replace_all();
+        for g in ["❤", "©", "1\u{20e3}", "❤\u{fe0e}"] {
+            assert!(detects(g, &policy), "{}", g);
+        }
+    }
+
+    #[test]
+    fn plain_text_is_not_emoji() {
+        let policy = DetectionPolicy::replace_all();
+        for g in ["a", "1", "#", "中", "\u{fe0f}"] {
+            assert!(!detects(g, &policy), "{}", g);
+        }
+    }
+
+    #[test]
+    fn min_version_is_an_exclusive_threshold() {
+        // 🥰 为 Emoji 11.0，🥱 为 12.0
+        assert_eq!(emojis::get("🥰").map(|e| e.unicode_version()), Some(UnicodeVersion::new(11, 0)));
+        assert_eq!(emojis::get("🥱").map(|e| e.unicode_version()), Some(UnicodeVersion::new(12, 0)));
+        let policy = DetectionPolicy { min_version: parse_unicode_version("11.0"), ..DetectionPolicy::default() };
+        assert!(!detects("🥰", &policy));
+        assert!(!detects("😀", &policy));
+        assert!(detects("🥱", &policy));
+    }
+
+    #[test]
+    fn unknown_version_is_not_filtered_by_min_version() {
+        use crate::emoji_data::EmojiTestData;
+        use std::sync::Arc;
+
+        // 不在 emojis 库中、emoji-test 注释里也没有版本号的条目
+        let g = "\u{1faff}";
+        assert!(emojis::get(g).is_none());
+        let data = EmojiTestData::parse("# group: Test\n# subgroup: test\n1FAFF ; fully-qualified # x new emoji\n");
+        let mut policy = DetectionPolicy { min_version: parse_unicode_version("99"), ..DetectionPolicy::default() };
+        policy.filter.data = Some(Arc::new(data));
+        assert!(detects(g, &policy));
+    }
+
+    #[test]
+    fn parses_unicode_versions() {
+        assert_eq!(parse_unicode_version("14"), Some(UnicodeVersion::new(14, 0)));
+        assert_eq!(parse_unicode_version(" 13.1 "), Some(UnicodeVersion::new(13, 1)));
+        assert_eq!(parse_unicode_version("x"), None);
+    }
+}
@@ -0,0 +1,97 @@
+//! Unicode `emoji-test.txt` 数据解析
+//!
+//! emojis 库不含子分组信息，子分组规则等需要用户提供 emoji-test.txt。
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::naming::code_without_fe0f;
+
+/// emoji-test.txt 中的限定状态
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Qualification {
+    Component,
+    FullyQualified,
+    MinimallyQualified,
+    Unqualified,
+}
+
+#[derive(Clone, Debug)]
+pub struct EmojiTestEntry {
+    /// 小写、`-` 连接的码点序列
+    pub code: String,
+    pub status: Qualification,
+    pub group: String,
+    pub subgroup: String,
+    /// Emoji 版本，如 (15, 1)
+    pub version: Option<(u32, u32)>,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EmojiTestData {
+    pub entries: Vec<EmojiTestEntry>,
+    by_code: HashMap<String, usize>,
+    /// 去掉 FE0F 后的 code -> 完全限定条目
+    fully_qualified: HashMap<String, usize>,
+}
+
+impl EmojiTestData {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("读取 emoji-test 数据失败: {}: {}", path.display(), e))?;
+        Ok(Self::parse(&content))
+    }
+
+    /// 解析 emoji-test.txt 内容，无法识别的行直接跳过
+    pub fn parse(content: &str) -> Self {
+        let mut data = EmojiTestData::default();
+        let (mut group, mut subgroup) = (String::new(), String::new());
+        for line in content.lines() {
+            let line = line.trim();
+            if let Some(g) = line.strip_prefix("# group:") {
+                group = g.trim().to_string();
+                continue;
+            }
+            if let Some(sg) = line.strip_prefix("# subgroup:") {
+                subgroup = sg.trim().to_string();
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((codes, rest)) = line.split_once(';') else { continue };
+            let (status, comment) = rest.split_once('#').unwrap_or((rest, ""));
+            let status = match status.trim() {
+                "component" => Qualification::Component,
+                "fully-qualified" => Qualification::FullyQualified,
+                "minimally-qualified" => Qualification::MinimallyQualified,
+                "unqualified" => Qualification::Unqualified,
+                _ => continue,
+            };
+            let code = codes.split_whitespace().map(|c| c.to_lowercase()).collect::<Vec<_>>().join("-");
+            // 注释格式：# 😀 E1.0 grinning face
+            let mut words = comment.split_whitespace().skip(1);
+            let version = words
+                .next()
+                .and_then(|v| v.strip_prefix('E'))
+                .and_then(|v| v.split_once('.'))
+                .and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?)));
+            let name = words.collect::<Vec<_>>().join(" ");
+            if status == Qualification::FullyQualified {
+                data.fully_qualified.insert(code_without_fe0f(&code), data.entries.len());
+            }
+            data.by_code.insert(code.clone(), data.entries.len());
+            data.entries.push(EmojiTestEntry { code, status, group: group.clone(), subgroup: subgroup.clone(), version, name });
+        }
+        data
+    }
+
+    pub fn get(&self, code: &str) -> Option<&EmojiTestEntry> {
+        self.by_code.get(code).map(|&i| &self.entries[i])
+    }
+
+    /// 任意限定状态（完全/最小限定、未限定）的 code 对应的完全限定条目
+    pub fn fully_qualified(&self, code: &str) -> Option<&EmojiTestEntry> {
+        self.fully_qualified.get(&code_without_fe0f(code)).map(|&i| &self.entries[i])
+    }
+}
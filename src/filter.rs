@@ -0,0 +1,224 @@
+//! 按码点、shortcode、分组、子分组筛选要替换的 emoji
+
+use std::sync::Arc;
+
+use emojis::Group;
+
+use crate::emoji_data::EmojiTestData;
+use crate::naming::{code_of, strip_selectors};
+
+/// 一条筛选规则
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EmojiRule {
+    /// 去掉变体选择符后的码点序列（小写、`-` 连接）
+    Sequence(String),
+    Group(Group),
+    /// emoji-test.txt 中的子分组名，如 `face-smiling`
+    Subgroup(String),
+}
+
+impl EmojiRule {
+    /// 解析规则：
+    /// - `group:Flags`、`group:"Smileys & Emotion"`
+    /// - `subgroup:heart`（需要 emoji-test 数据）
+    /// - `:heart:` shortcode
+    /// - `U+2764 U+FE0F` 或 `2764-fe0f` 码点序列
+    /// - 其他按 emoji 字面量处理
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        let rule = rule.trim();
+        if let Some(name) = rule.strip_prefix("group:") {
+            let name = name.trim().trim_matches('"');
+            return parse_group(name).map(EmojiRule::Group).ok_or_else(|| format!("未知分组: {}", name));
+        }
+        if let Some(name) = rule.strip_prefix("subgroup:") {
+            return Ok(EmojiRule::Subgroup(name.trim().trim_matches('"').to_lowercase()));
+        }
+        if rule.len() > 2 && rule.starts_with(':') && rule.ends_with(':') {
+            let name = &rule[1..rule.len() - 1];
+            let emoji = emojis::get_by_shortcode(name).ok_or_else(|| format!("未知 shortcode: {}", rule))?;
+            return Ok(EmojiRule::Sequence(base_code(emoji.as_str())));
+        }
+        if let Some(code) = parse_codepoints(rule) {
+            return Ok(EmojiRule::Sequence(base_code(&code)));
+        }
+        if rule.is_empty() {
+            return Err("空规则".to_string());
+        }
+        Ok(EmojiRule::Sequence(base_code(rule)))
+    }
+
+    fn matches(&self, g: &str, data: Option<&EmojiTestData>) -> bool {
+        match self {
+            EmojiRule::Sequence(code) => {
+                let base = base_code(g);
+                // 规则不带肤色时同时匹配各肤色变体
+                base == *code || (!has_skin_tone(code) && strip_skin_tones(&base) == *code)
+            }
+            EmojiRule::Group(group) => match emojis::get(g) {
+                Some(e) => e.group() == *group,
+                // emojis 库未收录的新 emoji 按 emoji-test 数据中的分组
+                None => data
+                    .and_then(|d| d.fully_qualified(&code_of(g)))
+                    .and_then(|e| parse_group(&e.group))
+                    .map(|g| g == *group)
+                    .unwrap_or(false),
+            },
+            EmojiRule::Subgroup(name) => data
+                .and_then(|d| {
+                    let code = code_of(g);
+                    d.get(&code).or_else(|| emojis::get(g).and_then(|e| d.get(&code_of(e.as_str()))))
+                })
+                .map(|e| e.subgroup.eq_ignore_ascii_case(name))
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// 包含/排除规则，include 为空时不限制
+#[derive(Clone, Debug, Default)]
+pub struct EmojiFilter {
+    pub include: Vec<EmojiRule>,
+    pub exclude: Vec<EmojiRule>,
+    /// 子分组规则使用的 emoji-test 数据
+    pub data: Option<Arc<EmojiTestData>>,
+}
+
+impl EmojiFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn allows(&self, g: &str) -> bool {
+        let data = self.data.as_deref();
+        if !self.include.is_empty() && !self.include.iter().any(|r| r.matches(g, data)) {
+            return false;
+        }
+        !self.exclude.iter().any(|r| r.matches(g, data))
+    }
+
+    /// 检查规则：没有 emoji-test 数据时子分组规则什么也匹配不到，报错而不是静默忽略
+    pub fn validate(&self) -> Result<(), String> {
+        if self.data.is_some() {
+            return Ok(());
+        }
+        match self.include.iter().chain(&self.exclude).find(|r| matches!(r, EmojiRule::Subgroup(_))) {
+            Some(EmojiRule::Subgroup(name)) => {
+                Err(format!("子分组规则 subgroup:{} 需要 emoji-test 数据（--emoji-test 或配置 emoji_test）", name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 解析逗号或换行分隔的规则列表
+    pub fn parse_rules(list: &str) -> Result<Vec<EmojiRule>, String> {
+        list.split([',', '\n'])
+            .filter(|r| !r.trim().is_empty())
+            .map(EmojiRule::parse)
+            .collect()
+    }
+}
+
+/// 解析分组名，如 `Flags`、`smileys-and-emotion`、`Smileys & Emotion`
+pub fn parse_group(name: &str) -> Option<Group> {
+    let normalize = |s: &str| {
+        s.replace('&', "and")
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let wanted = normalize(name);
+    Group::iter().find(|g| normalize(&format!("{:?}", g)) == wanted)
+}
+
+/// `U+1F600 U+FE0F`、`1f600-fe0f`、`1F600 FE0F` -> 对应字符串
+fn parse_codepoints(rule: &str) -> Option<String> {
+    let parts: Vec<&str> = rule.split(|c: char| c == '-' || c == '_' || c.is_whitespace()).filter(|p| !p.is_empty()).collect();
+    if parts.is_empty() {
+        return None;
+    }
+    parts
+        .iter()
+        .map(|p| {
+            let hex = p.strip_prefix("U+").or_else(|| p.strip_prefix("u+")).unwrap_or(p);
+            if hex.len() < 2 || hex.len() > 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+        })
+        .collect()
+}
+
+
+fn base_code(s: &str) -> String {
+    code_of(&strip_selectors(s))
+}
+
+fn is_skin_tone(cp: &str) -> bool {
+    matches!(cp, "1f3fb" | "1f3fc" | "1f3fd" | "1f3fe" | "1f3ff")
+}
+
+fn has_skin_tone(code: &str) -> bool {
+    code.split('-').any(is_skin_tone)
+}
+
+fn strip_skin_tones(code: &str) -> String {
+    code.split('-').filter(|c| !is_skin_tone(c)).collect::<Vec<_>>().join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence(code: &str) -> EmojiRule {
+        EmojiRule::Sequence(code.to_string())
+    }
+
+    #[test]
+    fn parses_each_rule_form() {
+        assert_eq!(EmojiRule::parse("group:Flags"), Ok(EmojiRule::Group(Group::Flags)));
+        assert_eq!(EmojiRule::parse(r#"group:"Smileys & Emotion""#), Ok(EmojiRule::Group(Group::SmileysAndEmotion)));
+        assert_eq!(EmojiRule::parse("group:smileys-and-emotion"), Ok(EmojiRule::Group(Group::SmileysAndEmotion)));
+        assert_eq!(EmojiRule::parse("subgroup:Face-Smiling"), Ok(EmojiRule::Subgroup("face-smiling".to_string())));
+        assert_eq!(EmojiRule::parse(":heart:"), Ok(sequence("2764")));
+        assert_eq!(EmojiRule::parse("U+2764 U+FE0F"), Ok(sequence("2764")));
+        assert_eq!(EmojiRule::parse("1f469-200d-1f4bb"), Ok(sequence("1f469-200d-1f4bb")));
+        assert_eq!(EmojiRule::parse(" 😀 "), Ok(sequence("1f600")));
+    }
+
+    #[test]
+    fn invalid_rules_are_errors() {
+        assert!(EmojiRule::parse("group:Nope").is_err());
+        assert!(EmojiRule::parse(":no_such_shortcode:").is_err());
+        assert!(EmojiRule::parse("").is_err());
+    }
+
+    #[test]
+    fn parse_rules_splits_on_commas_and_newlines() {
+        let rules = EmojiFilter::parse_rules("😀, group:Flags\n\n:heart:").unwrap();
+        assert_eq!(rules, [sequence("1f600"), EmojiRule::Group(Group::Flags), sequence("2764")]);
+        assert!(EmojiFilter::parse_rules("😀,group:Nope").is_err());
+    }
+
+    #[test]
+    fn rule_without_skin_tone_matches_all_tones() {
+        let filter = EmojiFilter { include: vec![sequence("1f44d")], ..Default::default() };
+        assert!(filter.allows("👍"));
+        assert!(filter.allows("👍🏽"));
+        assert!(!filter.allows("😀"));
+        let filter = EmojiFilter { exclude: vec![sequence("1f44d-1f3fd")], ..Default::default() };
+        assert!(!filter.allows("👍🏽"));
+        assert!(filter.allows("👍🏻"));
+    }
+
+    #[test]
+    fn subgroup_rules_need_emoji_test_data() {
+        let mut filter = EmojiFilter { exclude: vec![EmojiRule::parse("subgroup:heart").unwrap()], ..Default::default() };
+        assert!(filter.validate().is_err());
+        let data = "# group: Smileys & Emotion\n# subgroup: heart\n2764 FE0F ; fully-qualified # ❤️ E0.6 red heart\n";
+        filter.data = Some(Arc::new(EmojiTestData::parse(data)));
+        assert!(filter.validate().is_ok());
+        assert!(!filter.allows("❤\u{fe0f}"));
+        assert!(filter.allows("😀"));
+    }
+}
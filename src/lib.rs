@@ -1,13 +1,44 @@
 //! epub_emoji_x 库主入口
 
+#[cfg(feature = "bundled")]
+pub mod bundled;
+pub mod cache;
+pub mod capi;
+pub mod charref;
+pub mod config;
+pub mod coverage;
+pub mod detect;
+pub mod emoji_data;
+pub mod filter;
+pub mod log;
+pub mod naming;
+pub mod normalize;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+#[cfg(feature = "network")]
+pub mod prefetch;
+pub mod provider;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod render;
 pub mod replacer;
+pub mod restore;
+pub mod rewrite;
+pub mod scan;
+#[cfg(test)]
+mod testutil;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 use std::ffi::CStr;
-use crate::replacer::replace_emoji_in_epub_impl;
+use crate::replacer::{replace_emoji_in_epub_impl, replace_emoji_in_epub_with_options, ReplaceOptions};
 
-// 可供 FFI 调用的接口示例
+/// 可供 FFI 调用的接口示例
+///
+/// # Safety
+/// `input_path`、`output_path` 必须是有效的以 NUL 结尾的 C 字符串。
 #[export_name = "EpubEmojiX_replace_emoji_in_epub"]
-pub extern "C" fn replace_emoji_in_epub(input_path: *const std::os::raw::c_char, output_path: *const std::os::raw::c_char) -> i32 {
+pub unsafe extern "C" fn replace_emoji_in_epub(input_path: *const std::os::raw::c_char, output_path: *const std::os::raw::c_char) -> i32 {
     let input = unsafe { CStr::from_ptr(input_path) }.to_string_lossy();
     let output = unsafe { CStr::from_ptr(output_path) }.to_string_lossy();
     match replace_emoji_in_epub_impl(&input, &output) {
@@ -23,18 +54,79 @@ pub enum EmojiSourceMode {
 }
 
 /// 支持 FFI 调用的接口，带 emoji_source/emoji_dir
+///
+/// emoji_source：0 在线（缺图时下载），1 离线（只用缓存）；
+/// emoji_dir：缓存目录，传空指针使用 exe 所在目录下的 emoji_img。
+///
+/// # Safety
+/// `input_path`、`output_path` 必须是有效的 C 字符串，`emoji_dir` 为空或有效的 C 字符串。
 #[export_name = "EpubEmojiX_replace_emoji_in_epub_with_mode"]
-pub extern "C" fn replace_emoji_in_epub_with_mode(
+pub unsafe extern "C" fn replace_emoji_in_epub_with_mode(
     input_path: *const std::os::raw::c_char,
     output_path: *const std::os::raw::c_char,
-    _emoji_source: u32, // 保留参数但不使用
-    _emoji_dir: *const std::os::raw::c_char, // 保留参数但不使用
+    emoji_source: u32,
+    emoji_dir: *const std::os::raw::c_char,
 ) -> i32 {
-    use std::ffi::CStr;
     let input = unsafe { CStr::from_ptr(input_path) }.to_string_lossy();
     let output = unsafe { CStr::from_ptr(output_path) }.to_string_lossy();
-    match crate::replacer::replace_emoji_in_epub_impl(&input, &output) {
+    let mut opts = ReplaceOptions::default();
+    if emoji_source == 1 {
+        opts.source_mode = EmojiSourceMode::Local;
+    }
+    if !emoji_dir.is_null() {
+        let dir = unsafe { CStr::from_ptr(emoji_dir) }.to_string_lossy();
+        if !dir.is_empty() {
+            opts.cache_dir = Some(dir.into_owned().into());
+        }
+    }
+    match replace_emoji_in_epub_with_options(&input, &output, &opts) {
         Ok(_) => 0,
         Err(_) => 1,
     }
 }
+
+/// 支持 FFI 调用的接口，按配置文件设置图片来源、识别策略、包含/排除规则等
+///
+/// config_path 为空指针或空字符串时使用默认选项，配置格式见 [`config`]。
+///
+/// # Safety
+/// `input_path`、`output_path` 必须是有效的 C 字符串，`config_path` 为空或有效的 C 字符串。
+#[export_name = "EpubEmojiX_replace_emoji_in_epub_with_config"]
+pub unsafe extern "C" fn replace_emoji_in_epub_with_config(
+    input_path: *const std::os::raw::c_char,
+    output_path: *const std::os::raw::c_char,
+    config_path: *const std::os::raw::c_char,
+) -> i32 {
+    let input = unsafe { CStr::from_ptr(input_path) }.to_string_lossy();
+    let output = unsafe { CStr::from_ptr(output_path) }.to_string_lossy();
+    let mut opts = ReplaceOptions::default();
+    if !config_path.is_null() {
+        let config = unsafe { CStr::from_ptr(config_path) }.to_string_lossy();
+        if !config.is_empty() && config::apply_config_file(std::path::Path::new(config.as_ref()), &mut opts).is_err() {
+            return 2;
+        }
+    }
+    match replace_emoji_in_epub_with_options(&input, &output, &opts) {
+        Ok(_) => 0,
+        Err(_) => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn config_with_unusable_subgroup_rule_is_a_config_error() {
+        let dir = std::env::temp_dir().join(format!("epubemojix_config_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output, config) = (dir.join("in.epub"), dir.join("out.epub"), dir.join("sg.cfg"));
+        std::fs::write(&input, testutil::epub("<p>❤️</p>")).unwrap();
+        std::fs::write(&config, "exclude = subgroup:heart\n").unwrap();
+        let c = |p: &std::path::Path| CString::new(p.to_str().unwrap()).unwrap();
+        let rc = unsafe { replace_emoji_in_epub_with_config(c(&input).as_ptr(), c(&output).as_ptr(), c(&config).as_ptr()) };
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(rc, 2);
+    }
+}
@@ -0,0 +1,55 @@
+//! 日志输出
+//!
+//! 库内日志统一带 `[epub_emoji_x]` 前缀打印到标准输出，级别由 [`set_level`] 设置（全局）。
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 日志级别
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// 不输出
+    Quiet = 0,
+    /// 每个文件的汇总、缺图与回退、下载失败等
+    Normal = 1,
+    /// 另外输出每张图片的下载、写入等细节
+    Verbose = 2,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Level::Quiet,
+        1 => Level::Normal,
+        _ => Level::Verbose,
+    }
+}
+
+/// 当前级别是否输出 level 的日志
+pub fn enabled(level: Level) -> bool {
+    self::level() >= level
+}
+
+/// [`Level::Normal`] 日志
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Normal) {
+            println!("[epub_emoji_x] {}", format_args!($($arg)*));
+        }
+    };
+}
+
+/// [`Level::Verbose`] 日志
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Verbose) {
+            println!("[epub_emoji_x] {}", format_args!($($arg)*));
+        }
+    };
+}
@@ -1,27 +1,467 @@
-use clap::Parser;
-use epubemojix::replace_emoji_in_epub;
-use std::ffi::CString;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use epubemojix::cache::{default_cache_dir, export_pack, import_pack};
+use epubemojix::config::options_with_provider;
+use epubemojix::detect::{parse_unicode_version, DetectionPolicy};
+use epubemojix::emoji_data::EmojiTestData;
+use epubemojix::filter::EmojiRule;
+#[cfg(feature = "network")]
+use epubemojix::filter::parse_group;
+use epubemojix::log::{self, Level};
+use epubemojix::naming::strip_selectors;
+#[cfg(feature = "network")]
+use epubemojix::prefetch::{prefetch, PrefetchFilter, PrefetchStatus};
+use epubemojix::provider::{check_version, Provider};
+use epubemojix::render::EmojiFont;
+use epubemojix::replacer::{replace_emoji_in_epub_with_options, ReplaceOptions};
+use epubemojix::restore::restore_emoji_in_epub;
+use epubemojix::scan::{scan, scan_epub, EmojiMatch};
+use epubemojix::EmojiSourceMode;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-/// 命令行参数
+// 命令行参数：不带子命令时按 `process` 处理，拖拽单个 epub 到 exe 即 `process <文件>`
 #[derive(Parser)]
 #[command(author, version, about)]
-struct Args {
+struct Cli {
+    #[command(flatten)]
+    global: GlobalArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// 全局参数，可写在子命令前后
+#[derive(clap::Args)]
+struct GlobalArgs {
+    /// 配置文件（key = value 格式），命令行参数优先
+    #[arg(long = "config", global = true)]
+    config: Option<String>,
+    /// 图片集：twemoji、twemoji-fork、noto、openmoji，默认 twemoji
+    #[arg(long = "provider", global = true)]
+    provider: Option<String>,
+    /// 图片缓存目录，默认 exe 所在目录下的 emoji_img
+    #[arg(long = "cache-dir", global = true)]
+    cache_dir: Option<String>,
+    /// 输出更多日志（每张图片的下载、写入等）
+    #[arg(short = 'v', long = "verbose", global = true, action = clap::ArgAction::SetTrue, conflicts_with = "quiet")]
+    verbose: bool,
+    /// 只输出处理结果与错误
+    #[arg(short = 'q', long = "quiet", global = true, action = clap::ArgAction::SetTrue)]
+    quiet: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 替换 epub 中的 emoji 为图片（默认子命令）
+    Process(ProcessArgs),
+    /// 列出 epub/html 中的 emoji 及位置，不做修改
+    Scan(ScanArgs),
+    /// 把本工具插入的 emoji 图片换回文字
+    Restore(RestoreArgs),
+    /// 处理 html 或 xhtml 文件（直接替换，不打包为 epub）
+    Html(HtmlArgs),
+    /// emoji 图片缓存管理
+    Cache(CacheArgs),
+    /// 预下载全部 emoji 图片到缓存，供离线模式使用
+    #[cfg(feature = "network")]
+    Prefetch(PrefetchArgs),
+}
+
+const COMMANDS: &[&str] = &[
+    "process",
+    "scan",
+    "restore",
+    "html",
+    "cache",
+    #[cfg(feature = "network")]
+    "prefetch",
+    "help",
+];
+
+/// 图片集参数：尺寸与版本
+#[derive(clap::Args, Default)]
+struct SourceArgs {
+    /// 图片尺寸（像素），默认 72
+    #[arg(long = "size")]
+    size: Option<u32>,
+    /// 图片集版本（git 标签或分支），如 twemoji-fork 的 15.1.0
+    #[arg(long = "provider-version")]
+    provider_version: Option<String>,
+}
+
+/// 替换参数：图片集或字体，以及插入的 img 样式
+#[derive(clap::Args, Default)]
+struct ImageArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    /// 从彩色 emoji 字体（CBDT/sbix/COLR）生成图片，代替图片集，可用任意尺寸
+    #[arg(long = "emoji-font")]
+    emoji_font: Option<String>,
+    /// 插入的 img 标签的 style 属性，默认 height:1.3em
+    #[arg(long = "img-style")]
+    img_style: Option<String>,
+}
+
+impl GlobalArgs {
+    /// 替换用的选项（process、html、scan）
+    fn to_options(&self, image: &ImageArgs) -> Result<ReplaceOptions, String> {
+        let mut opts = self.base_options(&image.source)?;
+        if let Some(style) = &image.img_style {
+            opts.img_style = style.replace('"', "'");
+        }
+        if let Some(font) = &image.emoji_font {
+            opts.emoji_font = Some(EmojiFont::load(Path::new(font))?);
+        }
+        check_size(&opts)?;
+        Ok(opts)
+    }
+
+    /// 只针对图片集缓存的选项（prefetch、cache），不使用配置文件中的字体
+    fn source_options(&self, source: &SourceArgs) -> Result<ReplaceOptions, String> {
+        let mut opts = self.base_options(source)?;
+        opts.emoji_font = None;
+        check_size(&opts)?;
+        Ok(opts)
+    }
+
+    fn base_options(&self, source: &SourceArgs) -> Result<ReplaceOptions, String> {
+        let provider = match &self.provider {
+            Some(name) => Some(Provider::from_name(name).ok_or_else(|| format!("未知图片集: {}", name))?),
+            None => None,
+        };
+        let mut opts = options_with_provider(self.config.as_deref().map(Path::new), provider)?;
+        if let Some(version) = &source.provider_version {
+            check_version(version)?;
+            opts.provider_version = Some(version.clone());
+        }
+        if let Some(size) = source.size {
+            opts.size = size;
+        }
+        if let Some(dir) = &self.cache_dir {
+            opts.cache_dir = Some(dir.into());
+        }
+        Ok(opts)
+    }
+
+    fn log_level(&self) -> Level {
+        if self.quiet {
+            Level::Quiet
+        } else if self.verbose {
+            Level::Verbose
+        } else {
+            Level::Normal
+        }
+    }
+}
+
+/// emoji 识别策略参数
+#[derive(clap::Args)]
+struct DetectArgs {
+    /// 默认文本显示的符号（如 ❤、©、键帽）不带 FE0F 也替换
+    #[arg(long = "replace-text-default", default_value_t = false, action = clap::ArgAction::SetTrue)]
+    replace_text_default: bool,
+    /// 带 U+FE0E（文本显示）的字符也替换
+    #[arg(long = "ignore-text-selector", default_value_t = false, action = clap::ArgAction::SetTrue)]
+    ignore_text_selector: bool,
+    /// 保留为文本的符号列表文件（每行一个），替换默认列表
+    #[arg(long = "keep-text-file")]
+    keep_text_file: Option<String>,
+    /// 额外保留为文本的符号（可多次指定）
+    #[arg(long = "keep-text")]
+    keep_text: Vec<String>,
+    /// 只替换匹配的 emoji（可多次指定）：emoji 字面量、U+1F600、:shortcode:、group:Flags、subgroup:heart
+    #[arg(long = "include")]
+    include: Vec<String>,
+    /// 不替换匹配的 emoji（可多次指定），格式同 --include
+    #[arg(long = "exclude")]
+    exclude: Vec<String>,
+    /// Unicode emoji-test.txt，提供子分组等数据
+    #[arg(long = "emoji-test")]
+    emoji_test: Option<String>,
+    /// 只替换高于该 Unicode 版本的 emoji（如 11.0），更早的保持文本
+    #[arg(long = "min-version")]
+    min_version: Option<String>,
+    /// 阅读器字体（TTF/OTF/TTC，可多次指定），只替换字体中没有字形的 emoji
+    #[arg(long = "font")]
+    font: Vec<String>,
+    /// 同时使用 epub 内 @font-face 引用的字体判断
+    #[arg(long = "embedded-fonts", default_value_t = false, action = clap::ArgAction::SetTrue)]
+    embedded_fonts: bool,
+    /// 字体中的字形必须是彩色的才算覆盖
+    #[arg(long = "font-require-color", default_value_t = false, action = clap::ArgAction::SetTrue)]
+    font_require_color: bool,
+}
+
+impl DetectArgs {
+    fn apply(&self, policy: &mut DetectionPolicy) -> Result<(), String> {
+        if self.replace_text_default {
+            policy.text_default_requires_fe0f = false;
+        }
+        if self.ignore_text_selector {
+            policy.honor_text_selector = false;
+        }
+        if let Some(path) = &self.keep_text_file {
+            policy.keep_text = DetectionPolicy::load_keep_text(Path::new(path))?;
+        }
+        for symbol in &self.keep_text {
+            policy.keep_text.push(strip_selectors(symbol));
+        }
+        for rule in &self.include {
+            policy.filter.include.push(EmojiRule::parse(rule)?);
+        }
+        for rule in &self.exclude {
+            policy.filter.exclude.push(EmojiRule::parse(rule)?);
+        }
+        if let Some(v) = &self.min_version {
+            policy.min_version = Some(parse_unicode_version(v).ok_or_else(|| format!("无效版本号: {}", v))?);
+        }
+        for font in &self.font {
+            let n = policy.font_coverage.add_font_file(Path::new(font))?;
+            epubemojix::log_info!("加载字体: {}（{} 个）", font, n);
+        }
+        if self.embedded_fonts {
+            policy.embedded_fonts = true;
+        }
+        if self.font_require_color {
+            policy.font_coverage.require_color = true;
+        }
+        if let Some(path) = &self.emoji_test {
+            policy.filter.data = Some(Arc::new(EmojiTestData::load(Path::new(path))?));
+        }
+        policy.filter.validate()
+    }
+}
+
+/// process 子命令参数
+#[derive(clap::Args)]
+struct ProcessArgs {
+    /// 输入 epub 或目录，可有多个（拖拽多个文件/文件夹到 exe 时即此参数）
+    #[arg(value_name = "INPUT")]
+    files: Vec<String>,
     /// 输入文件、目录或文件列表（支持 @list.txt 格式）
+    #[arg(short = 'i', long = "input")]
+    input: Vec<String>,
+    /// 输出目录（多个输入共用）或输出文件（单文件模式），默认每个输入各自推导
+    #[arg(short = 'o', long = "output")]
+    output: Option<String>,
+    #[command(flatten)]
+    batch: BatchArgs,
+    #[command(flatten)]
+    image: ImageArgs,
+    #[command(flatten)]
+    detect: DetectArgs,
+    /// 离线模式：只使用缓存中已有的图片，不联网下载
+    #[arg(long = "offline", default_value_t = false, action = clap::ArgAction::SetTrue)]
+    offline: bool,
+}
+
+/// 批量处理参数：递归、文件筛选、输出文件名与冲突处理
+#[derive(clap::Args)]
+struct BatchArgs {
+    /// 递归处理子目录，输出时保留相对目录结构
+    #[arg(short = 'r', long = "recursive", default_value_t = false, action = clap::ArgAction::SetTrue)]
+    recursive: bool,
+    /// 目录中只处理匹配的文件（相对输入目录的路径，可多次指定），默认 *.epub
+    #[arg(long = "include-files", value_name = "GLOB")]
+    include_files: Vec<String>,
+    /// 跳过匹配的文件或子目录（可多次指定），如 "**/backup"
+    #[arg(long = "exclude-files", value_name = "GLOB")]
+    exclude_files: Vec<String>,
+    /// 输出文件名模板，可用 {stem}、{ext}、{name}，如 "{stem}.emoji.epub"
+    #[arg(long = "name-template", value_name = "TEMPLATE")]
+    name_template: Option<String>,
+    /// 输出文件已存在时的处理方式
+    #[arg(long = "on-conflict", value_enum, default_value_t = Conflict::Overwrite)]
+    on_conflict: Conflict,
+}
+
+/// 输出文件已存在（或与本批次其他文件重名）时的处理方式
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Conflict {
+    /// 跳过该文件
+    Skip,
+    /// 覆盖运行前已存在的文件；与本批次其他文件重名时加后缀，不互相覆盖
+    Overwrite,
+    /// 在文件名后加 _1、_2 等后缀
+    Rename,
+    /// 报错退出，不处理任何文件
+    Fail,
+}
+
+/// 按 --include-files/--exclude-files 筛选目录中的文件
+struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl FileFilter {
+    fn new(args: &BatchArgs) -> Result<Self, String> {
+        let include = if args.include_files.is_empty() { None } else { Some(build_globset(&args.include_files)?) };
+        Ok(FileFilter { include, exclude: build_globset(&args.exclude_files)? })
+    }
+
+    /// rel 为相对输入目录的路径
+    fn accepts(&self, rel: &Path) -> bool {
+        if self.exclude.is_match(rel) {
+            return false;
+        }
+        match &self.include {
+            Some(include) => include.is_match(rel),
+            None => is_epub(rel),
+        }
+    }
+
+    fn skips_dir(&self, rel: &Path) -> bool {
+        self.exclude.is_match(rel)
+    }
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| format!("无效的通配符: {}: {}", pattern, e))?);
+    }
+    builder.build().map_err(|e| format!("无效的通配符: {}", e))
+}
+
+/// scan 子命令参数
+#[derive(clap::Args)]
+struct ScanArgs {
+    /// epub、xhtml/html 或文本文件
+    #[arg(required = true)]
+    input: Vec<String>,
+    #[command(flatten)]
+    detect: DetectArgs,
+}
+
+/// restore 子命令参数
+#[derive(clap::Args)]
+struct RestoreArgs {
+    /// 处理过的 epub
+    input: String,
+    /// 输出 epub，默认 `{文件名}_restored.epub`
+    #[arg(short = 'o', long = "output")]
+    output: Option<String>,
+}
+
+/// html 子命令参数
+#[derive(clap::Args)]
+struct HtmlArgs {
+    /// 输入 html/xhtml 文件（可多次指定，支持 @list.txt 格式）
     #[arg(short = 'i', long = "input", required = true)]
     input: Vec<String>,
-    /// 输出目录（批量模式）或输出文件（单文件模式）
+    /// 输出文件（单文件）或目录（多文件）
     #[arg(short = 'o', long = "output", required = true)]
     output: String,
-    /// 处理 html 或 xhtml 文件（直接替换，不打包为epub）
-    #[arg(long = "html", default_value_t = false, action = clap::ArgAction::SetTrue)]
-    html: bool,
+    #[command(flatten)]
+    image: ImageArgs,
+    #[command(flatten)]
+    detect: DetectArgs,
+    /// 离线模式：只使用缓存中已有的图片，不联网下载
+    #[arg(long = "offline", default_value_t = false, action = clap::ArgAction::SetTrue)]
+    offline: bool,
+}
+
+/// prefetch 子命令参数
+#[cfg(feature = "network")]
+#[derive(clap::Args)]
+struct PrefetchArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    /// 只下载指定分组（可多次指定），如 Flags、"Smileys & Emotion"
+    #[arg(long = "group")]
+    group: Vec<String>,
+    /// 只下载不高于该 Unicode 版本的 emoji，如 14.0
+    #[arg(long = "max-version")]
+    max_version: Option<String>,
+    /// 按 Unicode emoji-test.txt 的列表下载（可包含更新的 emoji）
+    #[arg(long = "emoji-test")]
+    emoji_test: Option<String>,
+}
+
+/// cache 子命令参数
+#[derive(clap::Args)]
+struct CacheArgs {
+    #[command(subcommand)]
+    action: CacheAction,
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// 导入 Twemoji/Noto/OpenMoji 官方图片包或自制图片包（zip）到缓存
+    Import {
+        /// 图片包路径
+        archive: String,
+        /// --provider 为图片包所属图片集，--size 为要导入的尺寸
+        #[command(flatten)]
+        source: SourceArgs,
+    },
+    /// 把缓存中的图片导出为图片包（zip）
+    Export {
+        /// 输出的图片包路径
+        output: String,
+        #[command(flatten)]
+        source: SourceArgs,
+    },
+}
+
+fn run_cache(global: &GlobalArgs, args: CacheArgs) -> Result<(), String> {
+    match args.action {
+        CacheAction::Import { archive, source } => {
+            let opts = global.source_options(&source)?;
+            let cache_dir = opts.cache_dir.clone().unwrap_or_else(default_cache_dir);
+            let summary = import_pack(Path::new(&archive), &cache_dir, opts.provider, opts.size, opts.provider_version.as_deref())?;
+            println!(
+                "导入完成: {} {}px -> {}，新增 {}，跳过 {}",
+                summary.provider.name(), summary.size, summary.image_dir.display(), summary.imported, summary.skipped
+            );
+        }
+        CacheAction::Export { output, source } => {
+            let opts = global.source_options(&source)?;
+            let cache_dir = opts.cache_dir.clone().unwrap_or_else(default_cache_dir);
+            let count = export_pack(&cache_dir, opts.provider, opts.size, opts.provider_version.as_deref(), Path::new(&output))?;
+            println!("导出完成: {} 张图片 -> {}", count, output);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "network")]
+fn run_prefetch(global: &GlobalArgs, args: PrefetchArgs) -> Result<(), String> {
+    let mut opts = global.source_options(&args.source)?;
+    if let Some(path) = &args.emoji_test {
+        opts.detection.filter.data = Some(Arc::new(EmojiTestData::load(Path::new(path))?));
+    }
+    let mut filter = PrefetchFilter::default();
+    for name in &args.group {
+        filter.groups.push(parse_group(name).ok_or_else(|| format!("未知分组: {}", name))?);
+    }
+    if let Some(v) = &args.max_version {
+        filter.max_version = Some(parse_unicode_version(v).ok_or_else(|| format!("无效版本号: {}", v))?);
+    }
+    epubemojix::log_info!("预下载到: {}", opts.provider_image_dir().display());
+    let summary = prefetch(&opts, &filter, |p| {
+        if log::enabled(Level::Normal) && (p.status != PrefetchStatus::Cached || p.done % 200 == 0 || p.done == p.total) {
+            println!("[{}/{}] {:?} {} {}", p.done, p.total, p.status, p.code, p.emoji);
+        }
+    })?;
+    println!(
+        "预下载完成: 共 {} 个，已缓存 {}，新下载 {}，失败 {}",
+        summary.total, summary.cached, summary.downloaded, summary.failed.len()
+    );
+    if !summary.failed.is_empty() {
+        println!("失败列表已写入 prefetch_failed.txt，重新运行 prefetch 可继续");
+    }
+    Ok(())
 }
 
 fn expand_input_list(inputs: &[String]) -> Vec<String> {
     let mut result = Vec::new();
     for item in inputs {
-        if item.starts_with('@') {
-            if let Ok(list) = std::fs::read_to_string(&item[1..]) {
+        if let Some(list_path) = item.strip_prefix('@') {
+            if let Ok(list) = std::fs::read_to_string(list_path) {
                 for line in list.lines() {
                     let trimmed = line.trim();
                     if !trimmed.is_empty() {
@@ -36,167 +476,565 @@ fn expand_input_list(inputs: &[String]) -> Vec<String> {
     result
 }
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    // 拖拽或只输入 -i 文件时自动推导输出路径
-    if args.len() == 2 {
-        let input = &args[1];
-        let output = if input.ends_with(".epub") {
-            let stem = std::path::Path::new(input).file_stem().unwrap().to_string_lossy();
-            let parent = std::path::Path::new(input).parent().unwrap_or_else(|| std::path::Path::new("."));
-            format!("{}\\{}_out.epub", parent.display(), stem)
-        } else if input.starts_with('@') {
-            let outdir = std::path::Path::new(&input[1..]).parent().unwrap_or_else(|| std::path::Path::new("."));
-            format!("{}\\output", outdir.display())
-        } else if std::fs::metadata(input).map(|m| m.is_dir()).unwrap_or(false) {
-            format!("{}\\output", input)
-        } else {
-            format!("{}_out", input)
-        };
-        let input_c = CString::new(input.clone()).unwrap();
-        let output_c = CString::new(output.clone()).unwrap();
-        let code = replace_emoji_in_epub(input_c.as_ptr(), output_c.as_ptr());
-        if code == 0 {
-            println!("处理完成: {} -> {}", input, output);
-        } else {
-            eprintln!("处理失败，错误码: {}", code);
-        }
-        return;
-    }
-    // 兼容 -i 但无 -o 时，自动推导输出路径
-    if args.len() >= 3 && (args[1] == "-i" || args[1] == "--input") && !args.contains(&"-o".to_string()) && !args.contains(&"--output".to_string()) {
-        let input = &args[2];
-        let output = if input.ends_with(".epub") {
-            let stem = std::path::Path::new(input).file_stem().unwrap().to_string_lossy();
-            let parent = std::path::Path::new(input).parent().unwrap_or_else(|| std::path::Path::new("."));
-            format!("{}\\{}_out.epub", parent.display(), stem)
-        } else if input.starts_with('@') {
-            let outdir = std::path::Path::new(&input[1..]).parent().unwrap_or_else(|| std::path::Path::new("."));
-            format!("{}\\output", outdir.display())
-        } else if std::fs::metadata(input).map(|m| m.is_dir()).unwrap_or(false) {
-            format!("{}\\output", input)
-        } else {
-            format!("{}_out", input)
+/// 未指定 -o 时按输入推导输出路径：`book_out.epub`、列表文件或目录下的 `output`
+fn default_output(input: &str) -> PathBuf {
+    let path = Path::new(input);
+    if is_epub(path) {
+        let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        path.with_file_name(format!("{}_out.epub", stem))
+    } else if let Some(list_path) = input.strip_prefix('@') {
+        Path::new(list_path).parent().unwrap_or_else(|| Path::new("")).join("output")
+    } else if path.is_dir() {
+        path.join("output")
+    } else {
+        PathBuf::from(format!("{}_out", input))
+    }
+}
+
+/// 扩展名是否为 epub（不区分大小写）
+fn is_epub(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("epub"))
+}
+
+/// 输出目录 dir 下与 input 同名的文件
+fn output_in_dir(dir: &Path, input: &Path) -> PathBuf {
+    dir.join(input.file_name().unwrap_or(input.as_os_str()))
+}
+
+/// 创建输出文件所在的目录
+fn create_parent_dir(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => {
+            std::fs::create_dir_all(parent).map_err(|e| format!("创建输出目录失败: {}: {}", parent.display(), e))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn run_process(global: &GlobalArgs, args: ProcessArgs) -> Result<(), String> {
+    let raw_inputs: Vec<String> = args.files.into_iter().chain(args.input).collect();
+    if raw_inputs.is_empty() {
+        return Err("缺少输入文件，用法: EpubEmojiX <book.epub>... 或 EpubEmojiX process -i <输入> -o <输出>".to_string());
+    }
+    let mut opts = global.to_options(&args.image)?;
+    args.detect.apply(&mut opts.detection)?;
+    if args.offline {
+        opts.source_mode = EmojiSourceMode::Local;
+    }
+
+    let output = args.output.as_ref().map(PathBuf::from);
+    let jobs = collect_process_jobs(&raw_inputs, output.as_deref(), &args.batch)?;
+    if jobs.is_empty() {
+        println!("未找到 epub 文件: {}", raw_inputs.join(" "));
+        return Ok(());
+    }
+    let (mut failed, mut skipped) = (0, 0);
+    let mut planned = HashSet::new();
+    for (input, output_planned) in &jobs {
+        let Some(output) = resolve_conflict(output_planned, args.batch.on_conflict, &mut planned) else {
+            println!("跳过（输出已存在）: {} -> {}", input.display(), output_planned.display());
+            skipped += 1;
+            continue;
         };
-        let input_c = CString::new(input.clone()).unwrap();
-        let output_c = CString::new(output.clone()).unwrap();
-        let code = replace_emoji_in_epub(input_c.as_ptr(), output_c.as_ptr());
-        if code == 0 {
-            println!("处理完成: {} -> {}", input, output);
-        } else {
-            eprintln!("处理失败，错误码: {}", code);
+        if output != *output_planned {
+            println!("输出重名，改为: {} -> {}", input.display(), output.display());
+        }
+        if !process_one(input, &output, &opts) {
+            failed += 1;
         }
-        return;
     }
-    let args = Args::parse();
-    let input_list = expand_input_list(&args.input);
-    let output = &args.output;
-    use std::path::Path;
-
-    // 新增：处理 html/xhtml 文件
-    if args.html {
-        for (_, input) in input_list.iter().enumerate() {
-            let output_path = if input_list.len() == 1 {
-                output.clone()
-            } else {
-                // 多文件时输出到目录
-                let fname = Path::new(input).file_name().unwrap().to_string_lossy();
-                format!("{}\\{}", output, fname)
+    if jobs.len() > 1 {
+        println!(
+            "共 {} 个文件：成功 {} 个，失败 {} 个，跳过 {} 个",
+            jobs.len(),
+            jobs.len() - failed - skipped,
+            failed,
+            skipped
+        );
+    }
+    if failed > 0 {
+        return Err(format!("{} 个文件处理失败", failed));
+    }
+    Ok(())
+}
+
+/// 展开输入，得到 (输入, 输出) 列表
+///
+/// 指定 output 时：只有一个 epub 输入且 output 以 `.epub` 结尾则直接写到 output，否则 output 为共用的输出目录。
+/// 未指定时每个输入各自推导（见 [`default_output`]）。目录中的文件按相对路径放到输出目录下。
+/// 冲突策略为 fail 时，在处理任何文件前检查输出是否已存在或重名。
+fn collect_process_jobs(raw_inputs: &[String], output: Option<&Path>, batch: &BatchArgs) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let filter = FileFilter::new(batch)?;
+    let template = batch.name_template.as_deref();
+    let single_file = raw_inputs.len() == 1 && !raw_inputs[0].starts_with('@') && !Path::new(&raw_inputs[0]).is_dir();
+    let mut jobs = Vec::new();
+    for raw in raw_inputs {
+        let out_root = output.map(Path::to_path_buf).unwrap_or_else(|| default_output(raw));
+        let path = Path::new(raw);
+        if path.is_dir() {
+            // 目录批量模式
+            for rel in list_dir_files(path, &filter, batch.recursive, &out_root)? {
+                let out = mirrored_output(&out_root, &rel, template);
+                jobs.push((path.join(rel), out));
+            }
+        } else if raw.starts_with('@') {
+            for item in expand_input_list(std::slice::from_ref(raw)) {
+                let file = PathBuf::from(item);
+                let out = mirrored_output(&out_root, Path::new(file.file_name().unwrap_or(file.as_os_str())), template);
+                jobs.push((file, out));
+            }
+        } else if output.is_none() {
+            let out = match template {
+                Some(template) => path.with_file_name(output_file_name(path, template)),
+                None => out_root,
             };
-            match replace_emoji_in_html_file(input, &output_path) {
-                Ok(_) => println!("处理完成: {} -> {}", input, output_path),
-                Err(e) => eprintln!("处理失败: {} -> {}，错误: {}", input, output_path, e),
+            jobs.push((path.to_path_buf(), out));
+        } else if single_file && is_epub(&out_root) {
+            jobs.push((path.to_path_buf(), out_root));
+        } else {
+            let out = mirrored_output(&out_root, Path::new(path.file_name().unwrap_or(path.as_os_str())), template);
+            jobs.push((path.to_path_buf(), out));
+        }
+    }
+    if batch.on_conflict == Conflict::Fail {
+        let mut seen = HashSet::new();
+        for (_, out) in &jobs {
+            if out.exists() || !seen.insert(out) {
+                return Err(format!("输出文件已存在: {}", out.display()));
             }
         }
-        return;
-    }
-
-    if input_list.len() == 1 {
-        let input = &input_list[0];
-        let meta = std::fs::metadata(input);
-        if let Ok(meta) = meta {
-            if meta.is_dir() {
-                // 目录批量模式
-                let mut found = false;
-                for entry in std::fs::read_dir(input).unwrap() {
-                    let entry = entry.unwrap();
-                    let path = entry.path();
-                    if path.extension().map(|e| e == "epub").unwrap_or(false) {
-                        found = true;
-                        let input_path = path.to_string_lossy().to_string();
-                        let output_path = format!("{}\\{}", output, path.file_name().unwrap().to_string_lossy());
-                        let input_c = CString::new(input_path.clone()).unwrap();
-                        let output_c = CString::new(output_path.clone()).unwrap();
-                        let code = replace_emoji_in_epub(input_c.as_ptr(), output_c.as_ptr());
-                        if code == 0 {
-                            println!("处理完成: {} -> {}", input_path, output_path);
-                        } else {
-                            eprintln!("处理失败: {} -> {}，错误码: {}", input_path, output_path, code);
-                        }
-                    }
-                }
-                if !found {
-                    println!("未找到 epub 文件: {}", input);
+    }
+    Ok(jobs)
+}
+
+/// 列出目录中要处理的文件（相对 dir 的路径，已排序）
+///
+/// recursive 时进入子目录，但跳过输出目录 out_root（默认输出目录在输入目录内）与符号链接目录。
+fn list_dir_files(dir: &Path, filter: &FileFilter, recursive: bool, out_root: &Path) -> Result<Vec<PathBuf>, String> {
+    let out_root = out_root.canonicalize().ok();
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(rel_dir) = pending.pop() {
+        let full = dir.join(&rel_dir);
+        let entries = std::fs::read_dir(&full).map_err(|e| format!("读取目录失败: {}: {}", full.display(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("读取目录失败: {}: {}", full.display(), e))?;
+            let rel = rel_dir.join(entry.file_name());
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                if recursive && !filter.skips_dir(&rel) && entry.path().canonicalize().ok() != out_root {
+                    pending.push(rel);
                 }
-                return;
+            } else if filter.accepts(&rel) {
+                files.push(rel);
             }
         }
-        // 单文件模式
-        let output_path = if output.ends_with(".epub") {
-            output.clone()
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// 输出目录 root 下的对应文件：保留 rel 的相对目录，文件名按模板生成（默认与输入同名）
+fn mirrored_output(root: &Path, rel: &Path, template: Option<&str>) -> PathBuf {
+    let name = match template {
+        Some(template) => OsString::from(output_file_name(rel, template)),
+        None => rel.file_name().unwrap_or(rel.as_os_str()).to_os_string(),
+    };
+    match rel.parent() {
+        Some(parent) => root.join(parent).join(name),
+        None => root.join(name),
+    }
+}
+
+/// 按模板生成输出文件名：{stem} 为不含扩展名的文件名，{ext} 为扩展名，{name} 为完整文件名
+fn output_file_name(input: &Path, template: &str) -> String {
+    let text = |s: Option<&std::ffi::OsStr>| s.map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    template
+        .replace("{stem}", &text(input.file_stem()))
+        .replace("{ext}", &text(input.extension()))
+        .replace("{name}", &text(input.file_name()))
+}
+
+/// 按冲突策略确定实际输出路径，返回 None 表示跳过
+///
+/// planned 记录本批次已使用的输出路径。overwrite 只覆盖运行前已存在的文件，
+/// 与本批次其他文件重名时加 `_1`、`_2` 后缀，不会互相覆盖。
+fn resolve_conflict(output: &Path, policy: Conflict, planned: &mut HashSet<PathBuf>) -> Option<PathBuf> {
+    let taken = |path: &Path, planned: &HashSet<PathBuf>| path.exists() || planned.contains(path);
+    let resolved = match policy {
+        Conflict::Overwrite | Conflict::Fail if planned.contains(output) => numbered(output, |p| planned.contains(p)),
+        Conflict::Overwrite | Conflict::Fail => output.to_path_buf(),
+        Conflict::Skip if taken(output, planned) => return None,
+        Conflict::Skip => output.to_path_buf(),
+        Conflict::Rename => numbered(output, |p| taken(p, planned)),
+    };
+    planned.insert(resolved.clone());
+    Some(resolved)
+}
+
+/// 依次尝试 output、`stem_1.ext`、`stem_2.ext`……，返回第一个未被占用的路径
+fn numbered(output: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = output.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut candidate = output.to_path_buf();
+    let mut n = 1;
+    while taken(&candidate) {
+        candidate = output.with_file_name(format!("{}_{}{}", stem, n, ext));
+        n += 1;
+    }
+    candidate
+}
+
+/// 处理一个文件并打印结果，返回是否成功
+fn process_one(input: &Path, output: &Path, opts: &ReplaceOptions) -> bool {
+    match process_file(input, output, opts) {
+        Ok(_) => {
+            println!("处理完成: {} -> {}", input.display(), output.display());
+            true
+        }
+        Err(e) => {
+            eprintln!("处理失败: {} -> {}，错误: {}", input.display(), output.display(), e);
+            false
+        }
+    }
+}
+
+fn process_file(input: &Path, output: &Path, opts: &ReplaceOptions) -> Result<(), String> {
+    create_parent_dir(output)?;
+    replace_emoji_in_epub_with_options(&input.to_string_lossy(), &output.to_string_lossy(), opts).map(|_| ())
+}
+
+fn run_scan(global: &GlobalArgs, args: ScanArgs) -> Result<(), String> {
+    let mut opts = global.to_options(&ImageArgs::default())?;
+    args.detect.apply(&mut opts.detection)?;
+    let mut total = 0;
+    for input in expand_input_list(&args.input) {
+        let files = if is_epub(Path::new(&input)) {
+            let file = std::fs::File::open(&input).map_err(|e| format!("打开输入文件失败: {}: {}", input, e))?;
+            scan_epub(file, &opts.detection)?
+                .into_iter()
+                .map(|(name, matches)| (format!("{}:{}", input, name), matches))
+                .collect()
         } else {
-            let fname = Path::new(input).file_name().unwrap().to_string_lossy();
-            format!("{}\\{}", output, fname)
+            let content = std::fs::read_to_string(&input).map_err(|e| format!("读取文件失败: {}: {}", input, e))?;
+            vec![(input.clone(), scan(&content, &opts.detection).collect::<Vec<EmojiMatch>>())]
         };
-        let input_c = CString::new(input.clone()).unwrap();
-        let output_c = CString::new(output_path.clone()).unwrap();
-        let code = replace_emoji_in_epub(input_c.as_ptr(), output_c.as_ptr());
-        if code == 0 {
-            println!("处理完成: {} -> {}", input, output_path);
+        for (name, matches) in files {
+            for m in &matches {
+                println!(
+                    "{}:{}:{}\t{}\t{}\t{}",
+                    name, m.line, m.column, m.grapheme, m.code, m.name.as_deref().unwrap_or("")
+                );
+            }
+            total += matches.len();
+        }
+    }
+    println!("共 {} 个emoji", total);
+    Ok(())
+}
+
+fn run_restore(args: RestoreArgs) -> Result<(), String> {
+    let output = args.output.unwrap_or_else(|| {
+        let input = Path::new(&args.input);
+        let stem = input.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        input.with_file_name(format!("{}_restored.epub", stem)).to_string_lossy().to_string()
+    });
+    let report = restore_emoji_in_epub(&args.input, &output)?;
+    println!("还原完成: {} -> {}，{} 个emoji，删除 {} 张图片", args.input, output, report.restored, report.images_removed);
+    Ok(())
+}
+
+fn run_html(global: &GlobalArgs, args: HtmlArgs) -> Result<(), String> {
+    let input_list = expand_input_list(&args.input);
+    let mut opts = global.to_options(&args.image)?;
+    args.detect.apply(&mut opts.detection)?;
+    if args.offline {
+        opts.source_mode = EmojiSourceMode::Local;
+    }
+    for input in input_list.iter() {
+        let output_path = if input_list.len() == 1 {
+            PathBuf::from(&args.output)
         } else {
-            eprintln!("处理失败，错误码: {}", code);
+            // 多文件时输出到目录
+            output_in_dir(Path::new(&args.output), Path::new(input))
+        };
+        match replace_emoji_in_html_file(input, &output_path, &opts) {
+            Ok(_) => println!("处理完成: {} -> {}", input, output_path.display()),
+            Err(e) => eprintln!("处理失败: {} -> {}，错误: {}", input, output_path.display(), e),
         }
-        return;
-    } else {
-        // 多文件批量模式
-        for input in &input_list {
-            let fname = Path::new(input).file_name().unwrap().to_string_lossy();
-            let output_path = if output.ends_with(".epub") && input_list.len() == 1 {
-                output.clone()
-            } else {
-                format!("{}\\{}", output, fname)
-            };
-            let input_c = CString::new(input.clone()).unwrap();
-            let output_c = CString::new(output_path.clone()).unwrap();
-            let code = replace_emoji_in_epub(input_c.as_ptr(), output_c.as_ptr());
-            if code == 0 {
-                println!("处理完成: {} -> {}", input, output_path);
-            } else {
-                eprintln!("处理失败: {} -> {}，错误码: {}", input, output_path, code);
-            }
+    }
+    Ok(())
+}
+
+/// 没有子命令时补上 `process`，兼容拖拽与旧的 `-i/-o` 用法
+fn with_default_command(mut args: Vec<OsString>) -> Vec<OsString> {
+    let first_positional = first_positional(&args);
+    let has_options = args.iter().skip(1).any(|a| {
+        let s = a.to_string_lossy();
+        s.starts_with('-') && !matches!(s.as_ref(), "-h" | "--help" | "-V" | "--version")
+    });
+    let needs_default = match first_positional {
+        Some(p) => !COMMANDS.contains(&p.as_str()),
+        None => has_options,
+    };
+    if needs_default {
+        args.insert(1, OsString::from("process"));
+    }
+    args
+}
+
+/// 第一个不是选项、也不是选项值的参数
+fn first_positional(args: &[OsString]) -> Option<String> {
+    let takes_value = value_options();
+    let mut prev_takes_value = false;
+    for arg in args.iter().skip(1) {
+        let s = arg.to_string_lossy();
+        if prev_takes_value {
+            prev_takes_value = false;
+            continue;
+        }
+        if s.starts_with('-') {
+            prev_takes_value = takes_value.contains(s.as_ref());
+            continue;
         }
+        return Some(s.to_string());
+    }
+    None
+}
+
+/// 全局参数与 process 子命令中带值的选项（`-o`、`--config` 等），其后的参数是选项值
+fn value_options() -> HashSet<String> {
+    let cli = Cli::command();
+    let process = cli.find_subcommand("process").into_iter().flat_map(|c| c.get_arguments());
+    cli.get_arguments()
+        .chain(process)
+        .filter(|arg| arg.get_action().takes_values() && !arg.is_positional())
+        .flat_map(|arg| {
+            let short = arg.get_short().map(|c| format!("-{}", c));
+            let long = arg.get_long().map(|l| format!("--{}", l));
+            short.into_iter().chain(long)
+        })
+        .collect()
+}
+
+/// 图片集不提供该尺寸时报错（使用字体时不限尺寸）
+fn check_size(opts: &ReplaceOptions) -> Result<(), String> {
+    if opts.emoji_font.is_none() && !opts.provider.sizes().contains(&opts.size) {
+        return Err(format!("{} 不支持尺寸 {}，可选: {:?}", opts.provider.name(), opts.size, opts.provider.sizes()));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    #[cfg(not(feature = "network"))]
+    if first_positional(&args).as_deref() == Some("prefetch") {
+        eprintln!("prefetch 需要 network feature，当前构建未启用");
+        std::process::exit(2);
+    }
+    let cli = Cli::parse_from(with_default_command(args));
+    log::set_level(cli.global.log_level());
+    let global = &cli.global;
+    let result = match cli.command {
+        Command::Process(args) => run_process(global, args),
+        Command::Scan(args) => run_scan(global, args),
+        Command::Restore(args) => run_restore(args),
+        Command::Html(args) => run_html(global, args),
+        Command::Cache(args) => run_cache(global, args),
+        #[cfg(feature = "network")]
+        Command::Prefetch(args) => run_prefetch(global, args),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
     }
 }
 
 // 新增：处理 html/xhtml 文件的 emoji 替换
-fn replace_emoji_in_html_file(input_path: &str, output_path: &str) -> Result<(), String> {
+fn replace_emoji_in_html_file(input_path: &str, output_path: &Path, opts: &ReplaceOptions) -> Result<(), String> {
     use std::fs;
-    use std::path::Path;
 
     let content = fs::read_to_string(input_path).map_err(|e| format!("读取文件失败: {}", e))?;
     // 图片目录与输出文件同级 emoji_img
-    let _out_dir = Path::new(output_path).parent().unwrap_or_else(|| Path::new("."));
+    let _out_dir = output_path.parent().unwrap_or_else(|| Path::new("."));
     let imgdir = "emoji_img";
     let imgdir_rel = imgdir; // 相对路径
-    let replaced = epubemojix::replacer::replace_emoji_in_xhtml_with_imgdir(&content, imgdir_rel);
+    let replaced = epubemojix::replacer::replace_emoji_in_xhtml_with_options(&content, imgdir_rel, opts);
 
     // 确保 emoji_img 目录存在
-    let exe_dir = std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.to_path_buf())).unwrap_or_else(|| Path::new(".").to_path_buf());
-    let emoji_img_dir = exe_dir.join(imgdir);
+    let emoji_img_dir = opts.image_dir();
     if !emoji_img_dir.exists() {
         std::fs::create_dir_all(&emoji_img_dir).map_err(|e| format!("创建图片目录失败: {}", e))?;
     }
 
+    create_parent_dir(output_path)?;
     fs::write(output_path, replaced).map_err(|e| format!("写入输出文件失败: {}", e))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// 测试用临时目录，结束时删除
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("epubemojix_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn touch(&self, rel: &str) -> PathBuf {
+            let path = self.0.join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, b"").unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn batch(recursive: bool, name_template: Option<&str>) -> BatchArgs {
+        BatchArgs {
+            recursive,
+            include_files: Vec::new(),
+            exclude_files: Vec::new(),
+            name_template: name_template.map(str::to_string),
+            on_conflict: Conflict::Overwrite,
+        }
+    }
+
+    #[test]
+    fn epub_extension_is_case_insensitive() {
+        assert!(is_epub(Path::new("BOOK.EPUB")));
+        assert!(!is_epub(Path::new("book.epub.txt")));
+        assert_eq!(default_output("dir/BOOK.EPUB"), Path::new("dir/BOOK_out.epub"));
+    }
+
+    #[test]
+    fn name_template_placeholders() {
+        let input = Path::new("books/novel.epub");
+        assert_eq!(output_file_name(input, "{stem}.emoji.{ext}"), "novel.emoji.epub");
+        assert_eq!(output_file_name(input, "new_{name}"), "new_novel.epub");
+        assert_eq!(output_file_name(Path::new("README"), "{stem}-{ext}"), "README-");
+    }
+
+    #[test]
+    fn mirrored_output_keeps_relative_dirs() {
+        let root = Path::new("out");
+        assert_eq!(mirrored_output(root, Path::new("a/b.epub"), None), Path::new("out/a/b.epub"));
+        assert_eq!(mirrored_output(root, Path::new("b.epub"), Some("{stem}_x.epub")), Path::new("out/b_x.epub"));
+        assert_eq!(mirrored_output(root, Path::new("a/b.epub"), Some("{stem}_x.epub")), Path::new("out/a/b_x.epub"));
+    }
+
+    #[test]
+    fn rename_numbers_existing_and_planned_outputs() {
+        let dir = TempDir::new("rename");
+        let existing = dir.touch("book.epub");
+        let mut planned = HashSet::new();
+        assert_eq!(resolve_conflict(&existing, Conflict::Rename, &mut planned), Some(dir.0.join("book_1.epub")));
+        assert_eq!(resolve_conflict(&existing, Conflict::Rename, &mut planned), Some(dir.0.join("book_2.epub")));
+        let fresh = dir.0.join("other.epub");
+        assert_eq!(resolve_conflict(&fresh, Conflict::Rename, &mut planned), Some(fresh.clone()));
+        assert_eq!(resolve_conflict(&fresh, Conflict::Rename, &mut planned), Some(dir.0.join("other_1.epub")));
+    }
+
+    #[test]
+    fn skip_and_overwrite_policies() {
+        let dir = TempDir::new("skip");
+        let existing = dir.touch("book.epub");
+        let fresh = dir.0.join("new.epub");
+        let mut planned = HashSet::new();
+        assert_eq!(resolve_conflict(&existing, Conflict::Skip, &mut planned), None);
+        assert_eq!(resolve_conflict(&fresh, Conflict::Skip, &mut planned), Some(fresh.clone()));
+        assert_eq!(resolve_conflict(&fresh, Conflict::Skip, &mut planned), None);
+        assert_eq!(resolve_conflict(&existing, Conflict::Overwrite, &mut planned), Some(existing.clone()));
+    }
+
+    #[test]
+    fn same_named_inputs_into_one_dir_are_not_overwritten() {
+        let dir = TempDir::new("samename");
+        let a = dir.touch("a/x.epub");
+        let b = dir.touch("b/x.epub");
+        let out = dir.0.join("flat");
+        // 上次运行留下的输出照常覆盖
+        dir.touch("flat/x.epub");
+        let inputs = [a.to_string_lossy().to_string(), b.to_string_lossy().to_string()];
+        let jobs = collect_process_jobs(&inputs, Some(&out), &batch(false, None)).unwrap();
+        assert_eq!(jobs, [(a, out.join("x.epub")), (b, out.join("x.epub"))]);
+        let mut planned = HashSet::new();
+        let outputs: Vec<_> = jobs.iter().map(|(_, o)| resolve_conflict(o, Conflict::Overwrite, &mut planned)).collect();
+        assert_eq!(outputs, [Some(out.join("x.epub")), Some(out.join("x_1.epub"))]);
+    }
+
+    #[test]
+    fn rerun_does_not_pick_up_previous_output() {
+        let dir = TempDir::new("rerun");
+        dir.touch("a.epub");
+        dir.touch("sub/b.epub");
+        dir.touch("sub/notes.txt");
+        // 上次运行写出的默认输出目录
+        dir.touch("output/a.epub");
+        dir.touch("output/sub/b.epub");
+        let input = dir.0.to_string_lossy().to_string();
+        let jobs = collect_process_jobs(std::slice::from_ref(&input), None, &batch(true, None)).unwrap();
+        let out = dir.0.join("output");
+        assert_eq!(
+            jobs,
+            [(dir.0.join("a.epub"), out.join("a.epub")), (dir.0.join("sub/b.epub"), out.join("sub/b.epub"))]
+        );
+    }
+
+    #[test]
+    fn non_recursive_listing_and_file_filters() {
+        let dir = TempDir::new("filter");
+        dir.touch("a.epub");
+        dir.touch("C.EPUB");
+        dir.touch("backup/c.epub");
+        dir.touch("sub/b.epub");
+        let filter = FileFilter { include: None, exclude: build_globset(&["backup".to_string()]).unwrap() };
+        let out = dir.0.join("output");
+        assert_eq!(list_dir_files(&dir.0, &filter, false, &out).unwrap(), [PathBuf::from("C.EPUB"), PathBuf::from("a.epub")]);
+        assert_eq!(
+            list_dir_files(&dir.0, &filter, true, &out).unwrap(),
+            [PathBuf::from("C.EPUB"), PathBuf::from("a.epub"), PathBuf::from("sub/b.epub")]
+        );
+    }
+
+    fn default_command(args: &[&str]) -> Vec<String> {
+        let args = std::iter::once("EpubEmojiX").chain(args.iter().copied()).map(OsString::from).collect();
+        with_default_command(args).into_iter().skip(1).map(|a| a.to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn bare_paths_become_process() {
+        assert_eq!(default_command(&["a.epub", "dir"]), ["process", "a.epub", "dir"]);
+        assert_eq!(default_command(&["-i", "a.epub", "-o", "out"]), ["process", "-i", "a.epub", "-o", "out"]);
+        // 选项值不是子命令
+        assert_eq!(default_command(&["-o", "scan", "a.epub"]), ["process", "-o", "scan", "a.epub"]);
+        let cli = Cli::try_parse_from(std::iter::once("EpubEmojiX".to_string()).chain(default_command(&["a.epub", "b.epub"])))
+            .unwrap();
+        assert!(matches!(cli.command, Command::Process(args) if args.files == ["a.epub", "b.epub"]));
+    }
+
+    #[test]
+    fn global_options_before_subcommand_keep_the_subcommand() {
+        assert_eq!(default_command(&["--provider", "noto", "scan", "a.epub"]), ["--provider", "noto", "scan", "a.epub"]);
+        assert_eq!(default_command(&["-q", "--cache-dir", "c", "restore", "a.epub"]), ["-q", "--cache-dir", "c", "restore", "a.epub"]);
+        assert_eq!(default_command(&["--cache-dir", "c", "a.epub"]), ["process", "--cache-dir", "c", "a.epub"]);
+    }
+
+    #[test]
+    fn help_and_version_are_left_alone() {
+        for args in [&[][..], &["help"], &["-h"], &["--help"], &["-V"], &["--version"], &["help", "scan"]] {
+            assert_eq!(default_command(args), args, "{:?}", args);
+        }
+    }
+}
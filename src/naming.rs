@@ -0,0 +1,170 @@
+//! emoji 图片文件名映射
+//!
+//! 书内与本地缓存统一使用 `{code}.png`，code 为小写、`-` 连接的完全限定码点序列
+//! （如 `1f469-200d-1f4bb`、`2764-fe0f`）。各图片集上游的文件名规则不同：
+//!
+//! | 图片集 | 规则 | 示例 |
+//! |---|---|---|
+//! | Twemoji | 小写；不含 ZWJ 的序列去掉 FE0F | `2764.png`、`1f3f3-fe0f-200d-1f308.png` |
+//! | Noto | `emoji_u` 前缀，`_` 连接，至少 4 位，去掉 FE0F | `emoji_u00a9.png`、`emoji_u1f44d_1f3fb.png` |
+//! | OpenMoji | 大写，至少 4 位，保留 FE0F | `00A9.png`、`2764-FE0F.png` |
+
+use crate::emoji_data::EmojiTestData;
+use crate::normalize::canonical_code;
+use crate::provider::Provider;
+
+/// 字符串的码点序列（小写、`-` 连接），不做规范化
+pub fn code_of(s: &str) -> String {
+    s.chars().map(|c| format!("{:x}", c as u32)).collect::<Vec<_>>().join("-")
+}
+
+/// code 转回字符串，含无效码点时返回 None
+pub fn code_to_str(code: &str) -> Option<String> {
+    code.split('-')
+        .map(|c| u32::from_str_radix(c, 16).ok().and_then(char::from_u32))
+        .collect()
+}
+
+/// 去掉字符串中的变体选择符 FE0E/FE0F
+pub fn strip_selectors(s: &str) -> String {
+    s.chars().filter(|c| *c != '\u{fe0e}' && *c != '\u{fe0f}').collect()
+}
+
+/// 去掉字符串中的 FE0E（文本显示选择符），FE0E 不属于任何完全限定序列
+pub fn strip_text_selector(s: &str) -> String {
+    s.replace('\u{fe0e}', "")
+}
+
+/// 去掉 code 中满足 strip 的码点
+pub fn strip_code_points(code: &str, strip: impl Fn(u32) -> bool) -> String {
+    code.split('-')
+        .filter(|c| u32::from_str_radix(c, 16).map(|v| !strip(v)).unwrap_or(true))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 去掉 code 中的 FE0F，用于匹配上游/旧缓存中不带 FE0F 的文件名
+pub fn code_without_fe0f(code: &str) -> String {
+    strip_code_points(code, |c| c == 0xfe0f)
+}
+
+/// 去掉 code 中的变体选择符 FE0E/FE0F
+pub fn code_without_selectors(code: &str) -> String {
+    strip_code_points(code, |c| c == 0xfe0e || c == 0xfe0f)
+}
+
+/// 字素簇对应的规范 code，见 [`canonical_code`]
+pub fn grapheme_code(g: &str, data: Option<&EmojiTestData>) -> String {
+    canonical_code(g, data)
+}
+
+/// 书内/缓存中的图片文件名
+pub fn book_filename(code: &str) -> String {
+    format!("{}.png", code)
+}
+
+/// 书内/缓存文件名还原为 code
+pub fn code_from_book_filename(name: &str) -> Option<&str> {
+    name.strip_suffix(".png")
+}
+
+/// 图片集上游（CDN、官方图片包）的文件名
+pub fn source_filename(provider: Provider, code: &str) -> String {
+    let parts: Vec<&str> = code.split('-').collect();
+    match provider {
+        Provider::Twemoji | Provider::TwemojiFork => {
+            let keep_fe0f = parts.contains(&"200d");
+            let parts: Vec<&str> = parts.into_iter().filter(|c| keep_fe0f || *c != "fe0f").collect();
+            format!("{}.png", parts.join("-"))
+        }
+        Provider::Noto => {
+            let parts: Vec<String> = parts.iter().filter(|c| **c != "fe0f").map(|c| format!("{:0>4}", c)).collect();
+            format!("emoji_u{}.png", parts.join("_"))
+        }
+        Provider::OpenMoji => {
+            let parts: Vec<String> = parts.iter().map(|c| format!("{:0>4}", c.to_uppercase())).collect();
+            format!("{}.png", parts.join("-"))
+        }
+    }
+}
+
+/// 上游文件名（可带目录）还原为 code；文件名不符合规则时返回 None
+///
+/// 上游文件名可能缺少 FE0F，按 emojis 库补全为完全限定形式。
+pub fn code_from_source_filename(provider: Provider, name: &str) -> Option<String> {
+    let base = name.rsplit('/').next()?;
+    let stem = base.strip_suffix(".png").or_else(|| base.strip_suffix(".PNG"))?;
+    let stem = match provider {
+        Provider::Noto => stem.strip_prefix("emoji_u").unwrap_or(stem).replace('_', "-"),
+        Provider::Twemoji | Provider::TwemojiFork | Provider::OpenMoji => stem.to_string(),
+    };
+    let parts: Option<Vec<u32>> = stem
+        .split('-')
+        .map(|c| {
+            let valid = !c.is_empty() && c.len() <= 6 && c.chars().all(|ch| ch.is_ascii_hexdigit());
+            valid.then(|| u32::from_str_radix(c, 16).ok()).flatten()
+        })
+        .collect();
+    let code = parts?.iter().map(|v| format!("{:x}", v)).collect::<Vec<_>>().join("-");
+    match code_to_str(&code).and_then(|s| emojis::get(&s)) {
+        Some(e) => Some(code_of(e.as_str())),
+        None => Some(code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_round_trip() {
+        for s in ["😀", "❤\u{fe0f}", "👩\u{200d}💻", "🏳\u{fe0f}\u{200d}🌈", "1\u{fe0f}\u{20e3}"] {
+            assert_eq!(code_to_str(&code_of(s)).as_deref(), Some(s));
+        }
+        assert_eq!(code_of("👩\u{200d}💻"), "1f469-200d-1f4bb");
+        assert_eq!(code_to_str("d800"), None);
+        assert_eq!(code_to_str("zz"), None);
+    }
+
+    #[test]
+    fn book_filename_round_trip() {
+        let name = book_filename("2764-fe0f");
+        assert_eq!(name, "2764-fe0f.png");
+        assert_eq!(code_from_book_filename(&name), Some("2764-fe0f"));
+        assert_eq!(code_from_book_filename("2764-fe0f.svg"), None);
+    }
+
+    #[test]
+    fn source_filename_per_provider() {
+        assert_eq!(source_filename(Provider::Twemoji, "2764-fe0f"), "2764.png");
+        assert_eq!(source_filename(Provider::Twemoji, "1f3f3-fe0f-200d-1f308"), "1f3f3-fe0f-200d-1f308.png");
+        assert_eq!(source_filename(Provider::Noto, "a9-fe0f"), "emoji_u00a9.png");
+        assert_eq!(source_filename(Provider::Noto, "1f44d-1f3fb"), "emoji_u1f44d_1f3fb.png");
+        assert_eq!(source_filename(Provider::OpenMoji, "2764-fe0f"), "2764-FE0F.png");
+    }
+
+    #[test]
+    fn source_filename_round_trip_restores_fully_qualified_code() {
+        for provider in [Provider::Twemoji, Provider::TwemojiFork, Provider::Noto, Provider::OpenMoji] {
+            for code in ["1f600", "2764-fe0f", "a9-fe0f", "1f469-200d-1f4bb", "1f3f3-fe0f-200d-1f308", "1f44d-1f3fb"] {
+                let name = format!("assets/{}", source_filename(provider, code));
+                assert_eq!(code_from_source_filename(provider, &name).as_deref(), Some(code), "{:?} {}", provider, name);
+            }
+        }
+    }
+
+    #[test]
+    fn source_filename_that_is_not_a_code_is_rejected() {
+        assert_eq!(code_from_source_filename(Provider::Twemoji, "LICENSE.png"), None);
+        assert_eq!(code_from_source_filename(Provider::Twemoji, "1f600.svg"), None);
+        assert_eq!(code_from_source_filename(Provider::Noto, "emoji_u1f600_.png"), None);
+    }
+
+    #[test]
+    fn selectors_are_stripped_from_codes() {
+        assert_eq!(code_without_fe0f("1f3f3-fe0f-200d-1f308"), "1f3f3-200d-1f308");
+        assert_eq!(code_without_selectors("2764-fe0e"), "2764");
+        assert_eq!(strip_selectors("❤\u{fe0f}\u{fe0e}"), "❤");
+        assert_eq!(strip_text_selector("❤\u{fe0e}"), "❤");
+    }
+}
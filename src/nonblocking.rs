@@ -0,0 +1,271 @@
+//! 异步接口（`async` feature），供 tokio 服务中调用
+//!
+//! 同步接口缺图时用 `reqwest::blocking` 下载，在异步运行时中调用会 panic 或阻塞工作线程。
+//! 这里先扫描出需要的图片，在调用方的运行时上并发取图，再把解压、改写 epub 的同步部分
+//! 放进 `tokio::task::spawn_blocking`。必须在 tokio 运行时中调用。
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+
+use futures_util::stream::{self, StreamExt};
+
+use crate::naming;
+use crate::provider::{ensure_image, fallback_candidates, fetch_urls, load_image, CallbackSource};
+use crate::replacer::{collect_emoji_codes, replace_emoji_in_epub_with_source, ReplaceOptions, ReplaceReport};
+use crate::EmojiSourceMode;
+
+/// 默认同时取图的数量
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// 异步 emoji 图片来源
+pub trait AsyncImageSource: Sync {
+    /// 读取 code 对应的图片数据，需要时在此下载/生成；没有该图片时返回 None
+    fn load(&self, code: &str) -> impl Future<Output = Option<Vec<u8>>> + Send;
+}
+
+/// 按 [`ReplaceOptions`] 取图：内置图片包、本地缓存、字体，缺图时用异步 reqwest 下载并写入缓存
+pub struct OptionsAsyncSource {
+    opts: Arc<ReplaceOptions>,
+    client: reqwest::Client,
+}
+
+impl OptionsAsyncSource {
+    pub fn new(opts: ReplaceOptions) -> Self {
+        Self::with_client(opts, reqwest::Client::new())
+    }
+
+    /// 使用调用方的 reqwest::Client（代理、超时、连接池等）
+    pub fn with_client(opts: ReplaceOptions, client: reqwest::Client) -> Self {
+        OptionsAsyncSource { opts: Arc::new(opts), client }
+    }
+
+    async fn download(&self, code: &str) -> Option<Vec<u8>> {
+        for url in fetch_urls(self.opts.provider, self.opts.size, self.opts.image_version(), code) {
+            crate::log_debug!("下载emoji图片: {}", url);
+            match get(&self.client, &url).await {
+                Ok(data) => return Some(data),
+                Err(e) => crate::log_info!("下载失败: {}: {}", url, e),
+            }
+        }
+        None
+    }
+}
+
+impl AsyncImageSource for OptionsAsyncSource {
+    async fn load(&self, code: &str) -> Option<Vec<u8>> {
+        // 字体生成与离线模式不联网，与同步接口相同
+        let no_download = self.opts.emoji_font.is_some() || self.opts.source_mode == EmojiSourceMode::Local;
+        // 读缓存、从字体生成是同步文件 I/O 与绘制，放到阻塞线程池，不占用运行时线程
+        let (opts, owned_code) = (Arc::clone(&self.opts), code.to_string());
+        let local = tokio::task::spawn_blocking(move || {
+            load_image(&opts, &owned_code).or_else(|| {
+                if no_download && ensure_image(&opts, &owned_code) {
+                    load_image(&opts, &owned_code)
+                } else {
+                    None
+                }
+            })
+        })
+        .await
+        .ok()
+        .flatten();
+        if local.is_some() || no_download {
+            return local;
+        }
+        let data = self.download(code).await?;
+        let path = self.opts.image_dir().join(naming::book_filename(code));
+        // 写缓存失败不影响本次使用
+        if let Err(e) = write_cache(&path, &data).await {
+            crate::log_info!("写入图片失败: {}: {}", path.display(), e);
+        }
+        Some(data)
+    }
+}
+
+/// 由调用方的异步回调提供图片数据，参数为 code
+pub struct AsyncCallbackSource<F>(F);
+
+impl<F, Fut> AsyncCallbackSource<F>
+where
+    F: Fn(String) -> Fut + Sync,
+    Fut: Future<Output = Option<Vec<u8>>> + Send,
+{
+    pub fn new(callback: F) -> Self {
+        AsyncCallbackSource(callback)
+    }
+}
+
+impl<F, Fut> AsyncImageSource for AsyncCallbackSource<F>
+where
+    F: Fn(String) -> Fut + Sync,
+    Fut: Future<Output = Option<Vec<u8>>> + Send,
+{
+    fn load(&self, code: &str) -> impl Future<Output = Option<Vec<u8>>> + Send {
+        (self.0)(code.to_string())
+    }
+}
+
+async fn get(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    let resp = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    resp.bytes().await.map(|b| b.to_vec()).map_err(|e| e.to_string())
+}
+
+async fn write_cache(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, data).await
+}
+
+/// 按回退链为每个 code 取图，最多 concurrency 个同时进行
+///
+/// 返回实际取到的 code -> 图片数据（含回退后的 code 与 ZWJ 序列的组成部分），
+/// 交给 [`CallbackSource`] 即可得到与同步接口相同的回退结果。
+pub async fn load_images_async<S: AsyncImageSource>(
+    source: &S,
+    codes: impl IntoIterator<Item = String>,
+    concurrency: usize,
+) -> HashMap<String, Vec<u8>> {
+    stream::iter(codes)
+        .map(|code| resolve_async(source, code))
+        .buffer_unordered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+async fn resolve_async<S: AsyncImageSource>(source: &S, code: String) -> Vec<(String, Vec<u8>)> {
+    if let Some(found) = resolve_single_async(source, &code).await {
+        return vec![found];
+    }
+    let mut images = Vec::new();
+    if code.contains("-200d-") {
+        for part in code.split("-200d-") {
+            // 与同步接口相同，缺任何一个组成部分都不拆分显示
+            match resolve_single_async(source, part).await {
+                Some(found) => images.push(found),
+                None => return Vec::new(),
+            }
+        }
+    }
+    images
+}
+
+async fn resolve_single_async<S: AsyncImageSource>(source: &S, code: &str) -> Option<(String, Vec<u8>)> {
+    for (_, candidate) in fallback_candidates(code) {
+        if let Some(data) = source.load(&candidate).await {
+            return Some((candidate, data));
+        }
+    }
+    None
+}
+
+/// 异步处理内存中的 epub，图片从 source 取得，返回新 epub 字节与处理结果
+pub async fn replace_emoji_in_epub_async<S: AsyncImageSource>(
+    input: Vec<u8>,
+    opts: &ReplaceOptions,
+    source: &S,
+) -> Result<(Vec<u8>, ReplaceReport), String> {
+    let policy = opts.detection.clone();
+    let (input, codes) = tokio::task::spawn_blocking(move || {
+        let codes = collect_emoji_codes(Cursor::new(&input), &policy);
+        (input, codes)
+    })
+    .await
+    .map_err(|e| format!("扫描epub失败: {}", e))?;
+    let images = load_images_async(source, codes?, DEFAULT_CONCURRENCY).await;
+    let opts = opts.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut source = CallbackSource::new(|code: &str| images.get(code).cloned());
+        let mut output = Cursor::new(Vec::new());
+        let report = replace_emoji_in_epub_with_source(Cursor::new(input), &mut output, &opts, &mut source)?;
+        Ok((output.into_inner(), report))
+    })
+    .await
+    .map_err(|e| format!("处理epub失败: {}", e))?
+}
+
+/// 同 [`replace_emoji_in_epub_async`]，按 opts 取图（缓存、字体、异步下载）
+pub async fn replace_emoji_in_epub_bytes_async(input: Vec<u8>, opts: &ReplaceOptions) -> Result<(Vec<u8>, ReplaceReport), String> {
+    replace_emoji_in_epub_async(input, opts, &OptionsAsyncSource::new(opts.clone())).await
+}
+
+/// 异步处理 epub 文件，处理成功后才写出输出文件
+pub async fn replace_emoji_in_epub_file_async(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    opts: &ReplaceOptions,
+) -> Result<ReplaceReport, String> {
+    let input = tokio::fs::read(input_path.as_ref()).await.map_err(|e| format!("打开输入文件失败: {}", e))?;
+    let (output, report) = replace_emoji_in_epub_bytes_async(input, opts).await?;
+    tokio::fs::write(output_path.as_ref(), output).await.map_err(|e| format!("创建输出文件失败: {}", e))?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::provider::ImageFallback;
+    use crate::testutil;
+
+    fn available() -> HashMap<String, Vec<u8>> {
+        ["1f600", "2764", "1f469", "1f4bb", "1f44d"].into_iter().map(|code| (code.to_string(), code.as_bytes().to_vec())).collect()
+    }
+
+    #[tokio::test]
+    async fn async_source_resolves_like_the_sync_interface() {
+        // 😀 直接命中，❤️ 去掉 FE0F，👩‍💻 拆成组成部分，👍🏽 去掉肤色，🦖 缺图
+        let input = testutil::epub("<p>😀 ❤\u{fe0f} 👩\u{200d}💻 👍\u{1f3fd} 🦖</p>");
+        let images = Arc::new(available());
+        let source = AsyncCallbackSource::new(|code: String| {
+            let images = Arc::clone(&images);
+            async move { images.get(&code).cloned() }
+        });
+        let opts = ReplaceOptions::default();
+        let (_, report) = replace_emoji_in_epub_async(input.clone(), &opts, &source).await.unwrap();
+
+        let images = available();
+        let mut sync_source = CallbackSource::new(|code: &str| images.get(code).cloned());
+        let sync_report =
+            replace_emoji_in_epub_with_source(Cursor::new(input), &mut Cursor::new(Vec::new()), &opts, &mut sync_source).unwrap();
+        assert_eq!(report.images, sync_report.images);
+        assert_eq!(report.counts, sync_report.counts);
+        assert_eq!(report.images["2764-fe0f"].fallback, ImageFallback::NoVariationSelector);
+        assert_eq!(report.images["1f469-200d-1f4bb"].codes, ["1f469", "1f4bb"]);
+        assert_eq!(report.images["1f44d-1f3fd"].fallback, ImageFallback::NoSkinTone);
+        assert_eq!(report.images["1f996"].fallback, ImageFallback::Missing);
+    }
+
+    #[tokio::test]
+    async fn load_images_async_limits_concurrency() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let source = AsyncCallbackSource::new(|code: String| {
+            let (in_flight, peak) = (Arc::clone(&in_flight), Arc::clone(&peak));
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                for _ in 0..3 {
+                    tokio::task::yield_now().await;
+                }
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Some(code.into_bytes())
+            }
+        });
+        let codes: Vec<String> = (0x1f600..0x1f60a).map(|c| format!("{:x}", c)).collect();
+        let images = load_images_async(&source, codes.clone(), 4).await;
+        assert_eq!(images.len(), codes.len());
+        assert_eq!(images["1f605"], b"1f605");
+        assert_eq!(peak.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,76 @@
+//! emoji 序列规范化
+//!
+//! 图片文件名按完全限定形式命名，书中常见未限定/最小限定写法（`❤` 不带 FE0F、
+//! 键帽不带 FE0F、`🏳‍🌈` 缺少 FE0F），需要先映射到完全限定序列。
+
+use crate::emoji_data::EmojiTestData;
+use crate::naming::{code_of, strip_selectors, strip_text_selector};
+
+/// 字素簇对应的规范 code（小写、`-` 连接的完全限定码点序列）
+///
+/// 优先使用 emoji-test 数据，其次 emojis 库；都不认识时按原码点生成。
+/// FE0E 不属于任何完全限定序列，一并去掉。
+pub fn canonical_code(g: &str, data: Option<&EmojiTestData>) -> String {
+    let without_fe0e = strip_text_selector(g);
+    let code = code_of(&without_fe0e);
+    if let Some(entry) = data.and_then(|d| d.fully_qualified(&code)) {
+        return entry.code.clone();
+    }
+    match emojis::get(&without_fe0e).or_else(|| emojis::get(&strip_selectors(g))) {
+        Some(e) => code_of(e.as_str()),
+        None => code,
+    }
+}
+
+/// 只由区域指示符组成但不是有效旗帜（单个或无效组合）时，返回对应的字母
+pub fn regional_indicator_letters(g: &str) -> Option<String> {
+    const RI_A: u32 = 0x1f1e6;
+    let letters: Option<String> = g
+        .chars()
+        .map(|c| match c as u32 {
+            v @ RI_A..=0x1f1ff => char::from_u32('A' as u32 + v - RI_A),
+            _ => None,
+        })
+        .collect();
+    let letters = letters.filter(|l| !l.is_empty())?;
+    emojis::get(g).is_none().then_some(letters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unqualified_sequences_become_fully_qualified() {
+        assert_eq!(canonical_code("❤", None), "2764-fe0f");
+        assert_eq!(canonical_code("1\u{20e3}", None), "31-fe0f-20e3");
+        assert_eq!(canonical_code("🏳\u{200d}🌈", None), "1f3f3-fe0f-200d-1f308");
+        assert_eq!(canonical_code("😀", None), "1f600");
+    }
+
+    #[test]
+    fn text_selector_is_dropped() {
+        assert_eq!(canonical_code("❤\u{fe0e}", None), "2764-fe0f");
+    }
+
+    #[test]
+    fn unknown_sequence_keeps_its_code_points() {
+        assert_eq!(canonical_code("a", None), "61");
+    }
+
+    #[test]
+    fn emoji_test_data_takes_precedence() {
+        let data = EmojiTestData::parse(
+            "# group: Test\n# subgroup: test\n1FAE9 FE0F ; fully-qualified # x E99.0 test\n1FAE9 ; unqualified # x E99.0 test\n",
+        );
+        assert_eq!(canonical_code("\u{1fae9}", Some(&data)), "1fae9-fe0f");
+    }
+
+    #[test]
+    fn regional_indicators_outside_flags_become_letters() {
+        assert_eq!(regional_indicator_letters("🇦"), Some("A".to_string()));
+        assert_eq!(regional_indicator_letters("🇦🇦"), Some("AA".to_string()));
+        assert_eq!(regional_indicator_letters("🇨🇳"), None);
+        assert_eq!(regional_indicator_letters("a"), None);
+    }
+}
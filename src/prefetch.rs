@@ -0,0 +1,117 @@
+//! 预下载：把 emojis 库收录的全部 emoji 图片提前下载到缓存，供离线模式使用
+
+use emojis::{Group, UnicodeVersion};
+
+use crate::emoji_data::Qualification;
+use crate::filter::parse_group;
+use crate::naming::{code_of, code_to_str};
+use crate::replacer::ReplaceOptions;
+
+/// 预下载范围过滤
+#[derive(Clone, Debug, Default)]
+pub struct PrefetchFilter {
+    /// 只下载这些分组，为空时不过滤
+    pub groups: Vec<Group>,
+    /// 只下载不高于该 Unicode 版本的 emoji
+    pub max_version: Option<UnicodeVersion>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrefetchStatus {
+    /// 缓存中已存在，跳过
+    Cached,
+    Downloaded,
+    Failed,
+}
+
+/// 单个 emoji 的预下载进度
+#[derive(Debug)]
+pub struct PrefetchProgress<'a> {
+    pub done: usize,
+    pub total: usize,
+    pub emoji: &'a str,
+    pub code: &'a str,
+    pub status: PrefetchStatus,
+}
+
+#[derive(Debug, Default)]
+pub struct PrefetchSummary {
+    pub total: usize,
+    pub cached: usize,
+    pub downloaded: usize,
+    /// 下载失败的码点序列
+    pub failed: Vec<String>,
+}
+
+/// 按 opts 选定的图片集/尺寸预下载 emoji 图片（含各肤色变体）
+///
+/// 加载了 emoji-test 数据时按其中的完全限定条目下载，否则使用 emojis 库的列表。
+/// 已缓存的图片直接跳过，中断后重新运行即可续传；
+/// 失败列表写入图片目录下的 prefetch_failed.txt。
+/// 总是写入图片集的缓存目录，opts.emoji_font 不起作用。
+pub fn prefetch(
+    opts: &ReplaceOptions,
+    filter: &PrefetchFilter,
+    mut on_progress: impl FnMut(&PrefetchProgress),
+) -> Result<PrefetchSummary, String> {
+    let image_dir = opts.provider_image_dir();
+    std::fs::create_dir_all(&image_dir).map_err(|e| format!("创建图片目录失败: {}", e))?;
+
+    let mut list: Vec<String> = Vec::new();
+    if let Some(data) = opts.detection.filter.data.as_deref() {
+        // 使用运行时加载的 emoji-test 数据，可包含 emojis 库尚未收录的新 emoji
+        for entry in data.entries.iter().filter(|e| e.status == Qualification::FullyQualified) {
+            if !filter.groups.is_empty() && !parse_group(&entry.group).map(|g| filter.groups.contains(&g)).unwrap_or(false) {
+                continue;
+            }
+            if let (Some(max), Some((major, minor))) = (filter.max_version, entry.version) {
+                if UnicodeVersion::new(major, minor) > max {
+                    continue;
+                }
+            }
+            if let Some(s) = code_to_str(&entry.code) {
+                list.push(s);
+            }
+        }
+    } else {
+        for e in emojis::iter() {
+            if !filter.groups.is_empty() && !filter.groups.contains(&e.group()) {
+                continue;
+            }
+            if let Some(max) = filter.max_version {
+                if e.unicode_version() > max {
+                    continue;
+                }
+            }
+            match e.skin_tones() {
+                Some(tones) => list.extend(tones.map(|t| t.as_str().to_string())),
+                None => list.push(e.as_str().to_string()),
+            }
+        }
+    }
+
+    let mut summary = PrefetchSummary { total: list.len(), ..Default::default() };
+    for (i, emoji) in list.iter().enumerate() {
+        let code = code_of(emoji);
+        let status = if crate::cache::find_image(&image_dir, &code).is_some() {
+            summary.cached += 1;
+            PrefetchStatus::Cached
+        } else if crate::provider::fetch(opts.provider, opts.size, opts.image_version(), &image_dir, &code).is_ok() {
+            summary.downloaded += 1;
+            PrefetchStatus::Downloaded
+        } else {
+            summary.failed.push(code.clone());
+            PrefetchStatus::Failed
+        };
+        on_progress(&PrefetchProgress { done: i + 1, total: summary.total, emoji, code: &code, status });
+    }
+
+    let failed_list = image_dir.join("prefetch_failed.txt");
+    if summary.failed.is_empty() {
+        let _ = std::fs::remove_file(&failed_list);
+    } else {
+        std::fs::write(&failed_list, summary.failed.join("\n"))
+            .map_err(|e| format!("写入失败列表失败: {}", e))?;
+    }
+    Ok(summary)
+}
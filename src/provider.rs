@@ -0,0 +1,339 @@
+//! emoji 图片来源：内置图片包、本地缓存与 CDN 下载
+
+#[cfg(feature = "network")]
+use std::io::Write;
+#[cfg(feature = "network")]
+use std::path::{Path, PathBuf};
+
+use std::collections::HashMap;
+
+use crate::cache;
+use crate::naming::{self, code_of, code_to_str, code_without_selectors, strip_code_points};
+use crate::replacer::ReplaceOptions;
+
+/// 支持的 emoji 图片集
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Provider {
+    Twemoji,
+    /// 社区维护的 Twemoji 分支（jdecked/twemoji），收录 Emoji 15 以后的新 emoji
+    TwemojiFork,
+    Noto,
+    OpenMoji,
+}
+
+impl Provider {
+    pub const ALL: [Provider; 4] = [Provider::Twemoji, Provider::TwemojiFork, Provider::Noto, Provider::OpenMoji];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Provider::Twemoji => "twemoji",
+            Provider::TwemojiFork => "twemoji-fork",
+            Provider::Noto => "noto",
+            Provider::OpenMoji => "openmoji",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// 该图片集在 CDN 上提供的 png 尺寸
+    pub fn sizes(self) -> &'static [u32] {
+        match self {
+            Provider::Twemoji | Provider::TwemojiFork => &[72],
+            Provider::Noto => &[32, 72, 128, 512],
+            Provider::OpenMoji => &[72, 618],
+        }
+    }
+
+    pub fn default_size(self) -> u32 {
+        72
+    }
+
+    /// 默认使用的图片集版本（git 标签或分支）
+    pub fn default_version(self) -> &'static str {
+        match self {
+            Provider::Twemoji => "14.0.2",
+            Provider::TwemojiFork => "16.0.1",
+            Provider::Noto => "main",
+            Provider::OpenMoji => "15.0.0",
+        }
+    }
+
+    /// code 为小写、`-` 连接的码点序列（如 `1f469-200d-1f4bb`），version 见 [`Provider::default_version`]
+    pub fn url(self, code: &str, size: u32, version: &str) -> String {
+        let file = naming::source_filename(self, code);
+        match self {
+            Provider::Twemoji => format!(
+                "https://gcore.jsdelivr.net/gh/twitter/twemoji@{}/assets/{}x{}/{}",
+                version, size, size, file
+            ),
+            Provider::TwemojiFork => format!(
+                "https://gcore.jsdelivr.net/gh/jdecked/twemoji@{}/assets/{}x{}/{}",
+                version, size, size, file
+            ),
+            Provider::Noto => format!(
+                "https://gcore.jsdelivr.net/gh/googlefonts/noto-emoji@{}/png/{}/{}",
+                version, size, file
+            ),
+            Provider::OpenMoji => format!(
+                "https://gcore.jsdelivr.net/gh/hfg-gmuend/openmoji@{}/color/{}x{}/{}",
+                version, size, size, file
+            ),
+        }
+    }
+}
+
+/// 检查图片集版本号，只允许字母、数字与 `.-_`（用于 URL 与缓存目录名）
+pub fn check_version(version: &str) -> Result<(), String> {
+    let valid = !version.is_empty() && version.chars().all(|c| c.is_ascii_alphanumeric() || ".-_".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("无效图片集版本: {}", version))
+    }
+}
+
+/// 确保 code 对应的图片可用：内置图片包 → 本地缓存 → 在线模式下载
+///
+/// 设置了 emoji 字体时改为：本地缓存 → 从字体生成，不使用图片包和网络。
+pub fn ensure_image(opts: &ReplaceOptions, code: &str) -> bool {
+    if let Some(font) = &opts.emoji_font {
+        let image_dir = opts.image_dir();
+        if image_dir.join(naming::book_filename(code)).exists() {
+            return true;
+        }
+        let Some(png) = font.render_png(code, opts.size) else {
+            return false;
+        };
+        let path = image_dir.join(naming::book_filename(code));
+        return match std::fs::create_dir_all(&image_dir).and_then(|_| std::fs::write(&path, png)) {
+            Ok(()) => {
+                crate::log_debug!("从字体 {} 生成图片: {}", font.name(), path.display());
+                true
+            }
+            Err(e) => {
+                crate::log_info!("写入图片失败: {}: {}", path.display(), e);
+                false
+            }
+        };
+    }
+    #[cfg(feature = "bundled")]
+    if crate::bundled::contains(opts.provider, opts.size, opts.image_version(), code) {
+        return true;
+    }
+    let image_dir = opts.image_dir();
+    if cache::find_image(&image_dir, code).is_some() {
+        return true;
+    }
+    #[cfg(feature = "network")]
+    if opts.source_mode == crate::EmojiSourceMode::Online {
+        return fetch(opts.provider, opts.size, opts.image_version(), &image_dir, code).is_ok();
+    }
+    crate::log_info!("离线模式，缓存中缺少图片: {}", image_dir.join(naming::book_filename(code)).display());
+    false
+}
+
+/// 图片回退方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageFallback {
+    /// 原序列的图片
+    Exact,
+    /// 完全限定形式（补全 FE0F）
+    FullyQualified,
+    /// 去掉变体选择符 FE0E/FE0F
+    NoVariationSelector,
+    /// 去掉肤色修饰符 1F3FB-1F3FF
+    NoSkinTone,
+    /// 按 ZWJ 拆开，各组成部分并排显示（每个部分都有图片时）
+    Components,
+    /// 所有回退都失败，保留原文字
+    Missing,
+}
+
+/// 一个 emoji 序列最终使用的图片
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedImage {
+    pub fallback: ImageFallback,
+    /// 依次插入的图片 code，Components 时为多个，Missing 时为原 code（不插入图片）
+    pub codes: Vec<String>,
+}
+
+/// emoji 图片来源
+pub trait ImageSource {
+    /// code 对应的图片是否可用，需要时在此下载/生成
+    fn ensure(&mut self, code: &str) -> bool;
+    /// 读取 code 对应的图片数据
+    fn load(&mut self, code: &str) -> Option<Vec<u8>>;
+}
+
+/// 按 [`ReplaceOptions`] 从内置图片包、本地缓存、字体与网络取图
+pub struct OptionsSource<'a>(pub &'a ReplaceOptions);
+
+impl ImageSource for OptionsSource<'_> {
+    fn ensure(&mut self, code: &str) -> bool {
+        ensure_image(self.0, code)
+    }
+
+    fn load(&mut self, code: &str) -> Option<Vec<u8>> {
+        load_image(self.0, code)
+    }
+}
+
+/// 由调用方回调提供图片数据（如 WASM 中由 JS 提供），结果按 code 缓存
+pub struct CallbackSource<F> {
+    callback: F,
+    images: HashMap<String, Option<Vec<u8>>>,
+}
+
+impl<F: FnMut(&str) -> Option<Vec<u8>>> CallbackSource<F> {
+    pub fn new(callback: F) -> Self {
+        CallbackSource { callback, images: HashMap::new() }
+    }
+}
+
+impl<F: FnMut(&str) -> Option<Vec<u8>>> ImageSource for CallbackSource<F> {
+    fn ensure(&mut self, code: &str) -> bool {
+        if !self.images.contains_key(code) {
+            let data = (self.callback)(code);
+            self.images.insert(code.to_string(), data);
+        }
+        self.images[code].is_some()
+    }
+
+    fn load(&mut self, code: &str) -> Option<Vec<u8>> {
+        self.ensure(code);
+        self.images[code].clone()
+    }
+}
+
+/// 为 code 找到可用的图片，依次尝试：原序列 → 完全限定形式 → 去掉变体选择符
+/// → 去掉肤色修饰符 → 拆分 ZWJ 序列并排显示；都没有时为 [`ImageFallback::Missing`]
+pub fn resolve_image(opts: &ReplaceOptions, code: &str) -> ResolvedImage {
+    resolve_image_from(&mut OptionsSource(opts), code)
+}
+
+/// 同 [`resolve_image`]，图片从 source 取得
+pub fn resolve_image_from(source: &mut dyn ImageSource, code: &str) -> ResolvedImage {
+    if let Some((fallback, c)) = resolve_single(source, code) {
+        return ResolvedImage { fallback, codes: vec![c] };
+    }
+    let parts: Vec<&str> = code.split("-200d-").collect();
+    if parts.len() > 1 {
+        // 缺任何一个组成部分都不拆分显示，避免只显示半个 emoji
+        let codes: Option<Vec<String>> = parts.iter().map(|part| resolve_single(source, part).map(|(_, c)| c)).collect();
+        if let Some(codes) = codes {
+            return ResolvedImage { fallback: ImageFallback::Components, codes };
+        }
+    }
+    ResolvedImage { fallback: ImageFallback::Missing, codes: vec![code.to_string()] }
+}
+
+/// 为 codes 中尚未解析的 code 查找/下载图片，结果写入 resolved
+pub fn acquire_images<'a>(
+    opts: &ReplaceOptions,
+    codes: impl IntoIterator<Item = &'a String>,
+    resolved: &mut HashMap<String, ResolvedImage>,
+) {
+    acquire_images_from(&mut OptionsSource(opts), codes, resolved)
+}
+
+/// 同 [`acquire_images`]，图片从 source 取得
+pub fn acquire_images_from<'a>(
+    source: &mut dyn ImageSource,
+    codes: impl IntoIterator<Item = &'a String>,
+    resolved: &mut HashMap<String, ResolvedImage>,
+) {
+    for code in codes {
+        if !resolved.contains_key(code) {
+            resolved.insert(code.clone(), resolve_image_from(source, code));
+        }
+    }
+}
+
+fn resolve_single(source: &mut dyn ImageSource, code: &str) -> Option<(ImageFallback, String)> {
+    fallback_candidates(code).into_iter().find(|(_, candidate)| source.ensure(candidate))
+}
+
+/// 单个序列依次尝试的图片 code（去重、去空），不含拆分 ZWJ
+pub(crate) fn fallback_candidates(code: &str) -> Vec<(ImageFallback, String)> {
+    let mut candidates = vec![(ImageFallback::Exact, code.to_string())];
+    if let Some(e) = code_to_str(code).and_then(|s| emojis::get(&s)) {
+        candidates.push((ImageFallback::FullyQualified, code_of(e.as_str())));
+    }
+    let no_vs = code_without_selectors(code);
+    candidates.push((ImageFallback::NoVariationSelector, no_vs.clone()));
+    candidates.push((ImageFallback::NoSkinTone, strip_code_points(&no_vs, |c| (0x1f3fb..=0x1f3ff).contains(&c))));
+
+    let mut tried: Vec<String> = Vec::new();
+    candidates.retain(|(_, candidate)| {
+        if candidate.is_empty() || tried.contains(candidate) {
+            return false;
+        }
+        tried.push(candidate.clone());
+        true
+    });
+    candidates
+}
+
+/// 读取 code 对应的图片数据：内置图片包优先，其次本地缓存
+pub fn load_image(opts: &ReplaceOptions, code: &str) -> Option<Vec<u8>> {
+    #[cfg(feature = "bundled")]
+    if opts.emoji_font.is_none() {
+        if let Some(data) = crate::bundled::get(opts.provider, opts.size, opts.image_version(), code) {
+            return Some(data);
+        }
+    }
+    let path = cache::find_image(&opts.image_dir(), code)?;
+    std::fs::read(path).ok()
+}
+
+/// 从 CDN 下载 emoji 图片保存到 image_dir/{code}.png
+///
+/// 上游文件名按图片集规则由 [`naming::source_filename`] 生成；下载失败且序列带 FE0F 时
+/// 再尝试去掉 FE0F 的文件名。
+#[cfg(feature = "network")]
+pub fn fetch(provider: Provider, size: u32, version: &str, image_dir: &Path, code: &str) -> Result<PathBuf, String> {
+    let code = code.to_lowercase(); // 统一小写
+    let abs_path = image_dir.join(naming::book_filename(&code));
+    let urls = fetch_urls(provider, size, version, &code);
+    for (i, url) in urls.iter().enumerate() {
+        if i == 0 {
+            crate::log_debug!("下载emoji图片: {} -> {}", url, abs_path.display());
+        } else {
+            crate::log_debug!("尝试下载无 -fe0f 变体图片: {} -> {}", url, abs_path.display());
+        }
+        if download(url, &abs_path).is_ok() {
+            return Ok(abs_path);
+        }
+    }
+    Err(format!("下载失败: {}", urls[0]))
+}
+
+/// code 的下载地址，带 FE0F 时第二个为去掉 FE0F 的文件名
+#[cfg(feature = "network")]
+pub(crate) fn fetch_urls(provider: Provider, size: u32, version: &str, code: &str) -> Vec<String> {
+    let mut urls = vec![provider.url(code, size, version)];
+    let fallback_url = provider.url(&naming::code_without_fe0f(code), size, version);
+    if fallback_url != urls[0] {
+        urls.push(fallback_url);
+    }
+    urls
+}
+
+#[cfg(feature = "network")]
+fn download(url: &str, path: &Path) -> Result<(), String> {
+    let resp = reqwest::blocking::get(url).map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    let bytes = resp.bytes().map_err(|e| e.to_string())?;
+    if let Some(parent) = path.parent() {
+        if !parent.exists() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+    }
+    let mut file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    file.write_all(&bytes).map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,397 @@
+//! Python 绑定（`python` feature，用 maturin 构建）
+//!
+//! ```python
+//! import epubemojix
+//!
+//! opts = epubemojix.Options(provider="noto", cache_dir="/data/emoji", offline=True)
+//! report = epubemojix.process_file("in.epub", "out.epub", opts)
+//! data, report = epubemojix.process_bytes(open("in.epub", "rb").read(), opts)
+//! for m in epubemojix.scan("早上好 😀", opts):
+//!     print(m.line, m.column, m.code, m.name)
+//! ```
+//!
+//! 处理期间释放 GIL；失败时抛出 `epubemojix.EpubEmojiXError`。
+//! 默认不输出日志，可用 `epubemojix.set_log_level("normal")` 或 `"verbose"` 打开。
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::config::options_with_provider;
+use crate::detect::parse_unicode_version;
+use crate::filter::EmojiFilter;
+use crate::log::{self, Level};
+use crate::provider::{check_version, ImageFallback, Provider, ResolvedImage};
+use crate::replacer::{replace_emoji_in_epub_stream, replace_emoji_in_epub_with_options, ReplaceOptions, ReplaceReport};
+use crate::EmojiSourceMode;
+
+create_exception!(epubemojix, EpubEmojiXError, PyException, "处理 epub 或解析选项失败");
+
+fn to_py_err(e: String) -> PyErr {
+    EpubEmojiXError::new_err(e)
+}
+
+/// emoji 图片集
+#[pyclass(name = "Provider", module = "epubemojix", frozen, eq, hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PyProvider(Provider);
+
+#[pymethods]
+impl PyProvider {
+    #[new]
+    fn new(name: &str) -> PyResult<Self> {
+        Provider::from_name(name).map(PyProvider).ok_or_else(|| to_py_err(format!("未知图片集: {}", name)))
+    }
+
+    /// 所有图片集
+    #[staticmethod]
+    fn all() -> Vec<PyProvider> {
+        Provider::ALL.iter().map(|&p| PyProvider(p)).collect()
+    }
+
+    #[getter]
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    /// 支持的图片尺寸
+    #[getter]
+    fn sizes(&self) -> Vec<u32> {
+        self.0.sizes().to_vec()
+    }
+
+    #[getter]
+    fn default_size(&self) -> u32 {
+        self.0.default_size()
+    }
+
+    #[getter]
+    fn default_version(&self) -> &'static str {
+        self.0.default_version()
+    }
+
+    /// code 对应图片的下载地址
+    #[pyo3(signature = (code, size=None, version=None))]
+    fn url(&self, code: &str, size: Option<u32>, version: Option<&str>) -> String {
+        self.0.url(code, size.unwrap_or(self.0.default_size()), version.unwrap_or(self.0.default_version()))
+    }
+
+    fn __str__(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Provider('{}')", self.0.name())
+    }
+}
+
+/// provider 参数可以是 Provider 或名称
+#[derive(FromPyObject)]
+enum ProviderArg {
+    Provider(PyProvider),
+    Name(String),
+}
+
+impl ProviderArg {
+    fn provider(self) -> PyResult<Provider> {
+        match self {
+            ProviderArg::Provider(p) => Ok(p.0),
+            ProviderArg::Name(name) => PyProvider::new(&name).map(|p| p.0),
+        }
+    }
+}
+
+/// 处理选项，先应用 config 指定的配置文件，再应用其余参数
+#[pyclass(name = "Options", module = "epubemojix")]
+#[derive(Clone, Default)]
+pub struct PyOptions(ReplaceOptions);
+
+#[pymethods]
+impl PyOptions {
+    #[new]
+    #[pyo3(signature = (
+        provider=None, *, size=None, provider_version=None, cache_dir=None, offline=None, config=None,
+        img_style=None, replace_text_default=None, ignore_text_selector=None, min_version=None,
+        include=None, exclude=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        provider: Option<ProviderArg>,
+        size: Option<u32>,
+        provider_version: Option<String>,
+        cache_dir: Option<PathBuf>,
+        offline: Option<bool>,
+        config: Option<PathBuf>,
+        img_style: Option<String>,
+        replace_text_default: Option<bool>,
+        ignore_text_selector: Option<bool>,
+        min_version: Option<String>,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+    ) -> PyResult<Self> {
+        let provider = provider.map(|p| p.provider()).transpose()?;
+        let mut opts = options_with_provider(config.as_deref(), provider).map_err(to_py_err)?;
+        if let Some(version) = provider_version {
+            check_version(&version).map_err(to_py_err)?;
+            opts.provider_version = Some(version);
+        }
+        if let Some(size) = size {
+            opts.size = size;
+        }
+        if opts.emoji_font.is_none() && !opts.provider.sizes().contains(&opts.size) {
+            return Err(to_py_err(format!("{} 不支持尺寸 {}", opts.provider.name(), opts.size)));
+        }
+        if cache_dir.is_some() {
+            opts.cache_dir = cache_dir;
+        }
+        if let Some(offline) = offline {
+            opts.source_mode = if offline { EmojiSourceMode::Local } else { EmojiSourceMode::Online };
+        }
+        if let Some(style) = img_style {
+            opts.img_style = style.replace('"', "'");
+        }
+        if let Some(v) = replace_text_default {
+            opts.detection.text_default_requires_fe0f = !v;
+        }
+        if let Some(v) = ignore_text_selector {
+            opts.detection.honor_text_selector = !v;
+        }
+        if let Some(v) = min_version {
+            opts.detection.min_version = Some(parse_unicode_version(&v).ok_or_else(|| to_py_err(format!("无效版本号: {}", v)))?);
+        }
+        for rule in include.unwrap_or_default() {
+            opts.detection.filter.include.extend(EmojiFilter::parse_rules(&rule).map_err(to_py_err)?);
+        }
+        for rule in exclude.unwrap_or_default() {
+            opts.detection.filter.exclude.extend(EmojiFilter::parse_rules(&rule).map_err(to_py_err)?);
+        }
+        opts.detection.filter.validate().map_err(to_py_err)?;
+        Ok(PyOptions(opts))
+    }
+
+    #[getter]
+    fn provider(&self) -> PyProvider {
+        PyProvider(self.0.provider)
+    }
+
+    #[getter]
+    fn size(&self) -> u32 {
+        self.0.size
+    }
+
+    /// 实际使用的图片集版本
+    #[getter]
+    fn provider_version(&self) -> &str {
+        self.0.image_version()
+    }
+
+    #[getter]
+    fn offline(&self) -> bool {
+        self.0.source_mode == EmojiSourceMode::Local
+    }
+
+    #[getter]
+    fn img_style(&self) -> &str {
+        &self.0.img_style
+    }
+
+    /// 当前图片集/尺寸对应的本地图片目录
+    #[getter]
+    fn image_dir(&self) -> PathBuf {
+        self.0.image_dir()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Options(provider='{}', size={}, provider_version='{}', offline={})",
+            self.0.provider.name(),
+            self.0.size,
+            self.0.image_version(),
+            if self.offline() { "True" } else { "False" }
+        )
+    }
+}
+
+/// 一个 emoji 最终使用的图片
+#[pyclass(name = "ResolvedImage", module = "epubemojix", frozen, get_all)]
+#[derive(Clone)]
+pub struct PyResolvedImage {
+    /// exact、fully_qualified、no_variation_selector、no_skin_tone、components、missing
+    fallback: &'static str,
+    /// 依次插入的图片 code
+    codes: Vec<String>,
+}
+
+impl From<&ResolvedImage> for PyResolvedImage {
+    fn from(r: &ResolvedImage) -> Self {
+        let fallback = match r.fallback {
+            ImageFallback::Exact => "exact",
+            ImageFallback::FullyQualified => "fully_qualified",
+            ImageFallback::NoVariationSelector => "no_variation_selector",
+            ImageFallback::NoSkinTone => "no_skin_tone",
+            ImageFallback::Components => "components",
+            ImageFallback::Missing => "missing",
+        };
+        PyResolvedImage { fallback, codes: r.codes.clone() }
+    }
+}
+
+#[pymethods]
+impl PyResolvedImage {
+    fn __repr__(&self) -> String {
+        let codes: Vec<String> = self.codes.iter().map(|c| format!("'{}'", c)).collect();
+        format!("ResolvedImage(fallback='{}', codes=[{}])", self.fallback, codes.join(", "))
+    }
+}
+
+/// 处理结果汇总
+#[pyclass(name = "Report", module = "epubemojix", frozen, get_all)]
+pub struct PyReport {
+    /// code -> 替换次数
+    counts: HashMap<String, usize>,
+    /// code -> 实际使用的图片
+    images: HashMap<String, PyResolvedImage>,
+}
+
+impl From<ReplaceReport> for PyReport {
+    fn from(r: ReplaceReport) -> Self {
+        PyReport {
+            counts: r.counts,
+            images: r.images.iter().map(|(code, img)| (code.clone(), img.into())).collect(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyReport {
+    /// 替换的 emoji 总数
+    #[getter]
+    fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// 没有找到图片的 code
+    #[getter]
+    fn missing(&self) -> Vec<String> {
+        let mut codes: Vec<String> =
+            self.images.iter().filter(|(_, img)| img.fallback == "missing").map(|(code, _)| code.clone()).collect();
+        codes.sort();
+        codes
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Report(total={}, kinds={}, missing={})", self.total(), self.counts.len(), self.missing().len())
+    }
+}
+
+/// 一处 emoji，start/end 为 Python 字符串下标
+#[pyclass(name = "EmojiMatch", module = "epubemojix", frozen, get_all)]
+pub struct PyEmojiMatch {
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+    grapheme: String,
+    code: String,
+    codepoints: Vec<u32>,
+    name: Option<String>,
+    group: Option<String>,
+    version: Option<(u32, u32)>,
+}
+
+#[pymethods]
+impl PyEmojiMatch {
+    fn __repr__(&self) -> String {
+        format!("EmojiMatch(line={}, column={}, code='{}', grapheme='{}')", self.line, self.column, self.code, self.grapheme)
+    }
+}
+
+fn options_or_default(options: Option<PyRef<PyOptions>>) -> ReplaceOptions {
+    options.map(|o| o.0.clone()).unwrap_or_default()
+}
+
+/// 处理 epub 文件
+#[pyfunction]
+#[pyo3(signature = (input, output, options=None))]
+fn process_file(py: Python<'_>, input: PathBuf, output: PathBuf, options: Option<PyRef<PyOptions>>) -> PyResult<PyReport> {
+    let opts = options_or_default(options);
+    let (input, output) = (path_str(&input)?, path_str(&output)?);
+    py.allow_threads(|| replace_emoji_in_epub_with_options(&input, &output, &opts)).map(PyReport::from).map_err(to_py_err)
+}
+
+/// 处理内存中的 epub，返回 (新 epub 字节, Report)
+#[pyfunction]
+#[pyo3(signature = (data, options=None))]
+fn process_bytes<'py>(py: Python<'py>, data: &[u8], options: Option<PyRef<PyOptions>>) -> PyResult<(Bound<'py, PyBytes>, PyReport)> {
+    let opts = options_or_default(options);
+    let mut output = Cursor::new(Vec::new());
+    let report = py.allow_threads(|| replace_emoji_in_epub_stream(Cursor::new(data), &mut output, &opts)).map_err(to_py_err)?;
+    Ok((PyBytes::new(py, &output.into_inner()), report.into()))
+}
+
+/// 扫描文本中的 emoji（识别规则同替换）
+#[pyfunction]
+#[pyo3(signature = (text, options=None))]
+fn scan(text: &str, options: Option<PyRef<PyOptions>>) -> Vec<PyEmojiMatch> {
+    let opts = options_or_default(options);
+    // 字节偏移 -> 字符下标
+    let (mut byte, mut index) = (0, 0);
+    let mut char_index = |target: usize| {
+        index += text[byte..target].chars().count();
+        byte = target;
+        index
+    };
+    crate::scan::scan(text, &opts.detection)
+        .map(|m| PyEmojiMatch {
+            start: char_index(m.range.start),
+            end: char_index(m.range.end),
+            line: m.line,
+            column: m.column,
+            grapheme: m.grapheme,
+            code: m.code,
+            codepoints: m.codepoints,
+            name: m.name,
+            group: m.group,
+            version: m.version,
+        })
+        .collect()
+}
+
+/// 设置日志级别：quiet（默认）、normal、verbose
+#[pyfunction]
+fn set_log_level(level: &str) -> PyResult<()> {
+    let level = match level.to_ascii_lowercase().as_str() {
+        "quiet" => Level::Quiet,
+        "normal" => Level::Normal,
+        "verbose" => Level::Verbose,
+        _ => return Err(to_py_err(format!("未知日志级别: {}（可选 quiet、normal、verbose）", level))),
+    };
+    log::set_level(level);
+    Ok(())
+}
+
+fn path_str(path: &Path) -> PyResult<String> {
+    path.to_str().map(str::to_string).ok_or_else(|| to_py_err(format!("路径不是有效的 UTF-8: {}", path.display())))
+}
+
+#[pymodule]
+fn epubemojix(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    // 库日志打印到标准输出，嵌入 Python 程序时默认关闭
+    log::set_level(Level::Quiet);
+    m.add("EpubEmojiXError", m.py().get_type::<EpubEmojiXError>())?;
+    m.add_class::<PyProvider>()?;
+    m.add_class::<PyOptions>()?;
+    m.add_class::<PyResolvedImage>()?;
+    m.add_class::<PyReport>()?;
+    m.add_class::<PyEmojiMatch>()?;
+    m.add_function(wrap_pyfunction!(process_file, m)?)?;
+    m.add_function(wrap_pyfunction!(process_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(scan, m)?)?;
+    m.add_function(wrap_pyfunction!(set_log_level, m)?)?;
+    Ok(())
+}
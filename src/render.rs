@@ -0,0 +1,345 @@
+//! 从本机彩色 emoji 字体生成图片
+//!
+//! CBDT/sbix 位图字形取出最接近的 png 再缩放到目标尺寸，COLR 字形直接绘制，均用 tiny-skia
+//! （需要 `render` feature；未启用时只能使用尺寸正好一致的位图）。
+//! SVG 字形暂不支持。
+
+use std::path::Path;
+use std::sync::Arc;
+
+use ttf_parser::Face;
+
+use crate::coverage::sequence_glyph;
+use crate::naming::code_to_str;
+
+/// 彩色 emoji 字体
+#[derive(Clone)]
+pub struct EmojiFont {
+    name: String,
+    data: Arc<Vec<u8>>,
+    index: u32,
+}
+
+impl std::fmt::Debug for EmojiFont {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EmojiFont({}#{})", self.name, self.index)
+    }
+}
+
+impl EmojiFont {
+    /// 加载字体文件，TTC 中使用第一个含彩色表的字体
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| format!("读取字体失败: {}: {}", path.display(), e))?;
+        let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        Self::from_data(&name, data)
+    }
+
+    pub fn from_data(name: &str, data: Vec<u8>) -> Result<Self, String> {
+        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        let index = (0..count)
+            .find(|&i| Face::parse(&data, i).map(|f| has_color_tables(&f)).unwrap_or(false))
+            .ok_or_else(|| format!("字体中没有 CBDT/sbix/COLR 彩色字形: {}", name))?;
+        Ok(EmojiFont { name: name.to_string(), data: Arc::new(data), index })
+    }
+
+    /// 字体名（文件名去掉扩展名），用于缓存目录
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 把 code（小写、`-` 连接的码点序列）渲染为 size×size 像素的 png
+    pub fn render_png(&self, code: &str, size: u32) -> Option<Vec<u8>> {
+        let face = Face::parse(&self.data, self.index).ok()?;
+        let g = code_to_str(code)?;
+        let glyph = sequence_glyph(&face, &g)?;
+        if let Some(image) = face.glyph_raster_image(glyph, size.min(u16::MAX as u32) as u16) {
+            if image.format == ttf_parser::RasterImageFormat::PNG {
+                #[cfg(feature = "render")]
+                return scale_png(image.data, size);
+                #[cfg(not(feature = "render"))]
+                return (u32::from(image.width.max(image.height)) == size).then(|| image.data.to_vec());
+            }
+        }
+        #[cfg(feature = "render")]
+        if face.is_color_glyph(glyph) {
+            return colr::render(&face, glyph, size);
+        }
+        None
+    }
+}
+
+/// 把位图字形（如 Noto Color Emoji 的 136×128）等比缩放到 size×size 并居中
+#[cfg(feature = "render")]
+fn scale_png(data: &[u8], size: u32) -> Option<Vec<u8>> {
+    use tiny_skia::{FilterQuality, Pixmap, PixmapPaint, Transform};
+
+    let src = Pixmap::decode_png(data).ok()?;
+    if src.width() == size && src.height() == size {
+        return Some(data.to_vec());
+    }
+    let scale = size as f32 / src.width().max(src.height()) as f32;
+    let x = (size as f32 - src.width() as f32 * scale) / 2.0;
+    let y = (size as f32 - src.height() as f32 * scale) / 2.0;
+    let mut dst = Pixmap::new(size, size)?;
+    let paint = PixmapPaint { quality: FilterQuality::Bicubic, ..PixmapPaint::default() };
+    dst.draw_pixmap(0, 0, src.as_ref(), &paint, Transform::from_row(scale, 0.0, 0.0, scale, x, y), None);
+    dst.encode_png().ok()
+}
+
+fn has_color_tables(face: &Face) -> bool {
+    let t = face.tables();
+    t.cbdt.is_some() || t.sbix.is_some() || t.colr.is_some()
+}
+
+#[cfg(feature = "render")]
+mod colr {
+    use tiny_skia::{
+        BlendMode, FillRule, GradientStop, LinearGradient, Mask, Paint, Path, PathBuilder, Pixmap, PixmapPaint,
+        Point, RadialGradient, Shader, SpreadMode, Transform,
+    };
+    use ttf_parser::colr::{self, CompositeMode, GradientExtend};
+    use ttf_parser::{Face, GlyphId, RgbaColor};
+
+    /// 绘制 COLR 字形：字形 em 框缩放到 size×size，水平居中
+    pub(super) fn render(face: &Face, glyph: GlyphId, size: u32) -> Option<Vec<u8>> {
+        let height = (face.ascender() as f32 - face.descender() as f32).max(1.0);
+        let scale = size as f32 / height;
+        let advance = face.glyph_hor_advance(glyph).unwrap_or(face.units_per_em()) as f32;
+        let base = Transform::from_row(scale, 0.0, 0.0, -scale, (size as f32 - advance * scale) / 2.0, face.ascender() as f32 * scale);
+        let mut painter = SkiaPainter {
+            face,
+            layers: vec![(Pixmap::new(size, size)?, BlendMode::SourceOver)],
+            transforms: vec![base],
+            clips: vec![None],
+            outline: None,
+        };
+        face.paint_color_glyph(glyph, 0, RgbaColor::new(0, 0, 0, 255), &mut painter)?;
+        painter.layers.pop()?.0.encode_png().ok()
+    }
+
+    struct SkiaPainter<'f, 'a> {
+        face: &'f Face<'a>,
+        layers: Vec<(Pixmap, BlendMode)>,
+        transforms: Vec<Transform>,
+        clips: Vec<Option<Mask>>,
+        outline: Option<Path>,
+    }
+
+    impl SkiaPainter<'_, '_> {
+        fn transform(&self) -> Transform {
+            *self.transforms.last().unwrap()
+        }
+
+        fn size(&self) -> (u32, u32) {
+            let p = &self.layers[0].0;
+            (p.width(), p.height())
+        }
+    }
+
+    impl<'a> colr::Painter<'a> for SkiaPainter<'_, 'a> {
+        fn outline_glyph(&mut self, glyph_id: GlyphId) {
+            let mut builder = Builder(PathBuilder::new());
+            self.outline = self.face.outline_glyph(glyph_id, &mut builder).and_then(|_| builder.0.finish());
+        }
+
+        fn paint(&mut self, paint: colr::Paint<'a>) {
+            let transform = self.transform();
+            let shader = match shader(&paint, transform) {
+                Some(s) => s,
+                None => return,
+            };
+            let sk_paint = Paint { shader, anti_alias: true, ..Paint::default() };
+            let mask = self.clips.last().and_then(|m| m.as_ref());
+            let (w, h) = self.size();
+            let path = match &self.outline {
+                Some(p) => p.clone(),
+                // 没有轮廓时填满整个画布（受裁剪约束）
+                None => match tiny_skia::Rect::from_xywh(0.0, 0.0, w as f32, h as f32) {
+                    Some(r) => {
+                        let layer = &mut self.layers.last_mut().unwrap().0;
+                        layer.fill_rect(r, &sk_paint, Transform::identity(), mask);
+                        return;
+                    }
+                    None => return,
+                },
+            };
+            let layer = &mut self.layers.last_mut().unwrap().0;
+            layer.fill_path(&path, &sk_paint, FillRule::Winding, transform, mask);
+        }
+
+        fn push_clip(&mut self) {
+            let (w, h) = self.size();
+            let transform = self.transform();
+            let mut mask = match self.clips.last().cloned().flatten() {
+                Some(m) => m,
+                None => {
+                    let mut m = Mask::new(w, h).unwrap();
+                    m.data_mut().fill(255);
+                    m
+                }
+            };
+            if let Some(path) = &self.outline {
+                mask.intersect_path(path, FillRule::Winding, true, transform);
+            }
+            self.clips.push(Some(mask));
+        }
+
+        fn push_clip_box(&mut self, clipbox: colr::ClipBox) {
+            let rect = tiny_skia::Rect::from_ltrb(clipbox.x_min, clipbox.y_min, clipbox.x_max, clipbox.y_max);
+            let saved = self.outline.take();
+            self.outline = rect.map(PathBuilder::from_rect);
+            self.push_clip();
+            self.outline = saved;
+        }
+
+        fn pop_clip(&mut self) {
+            if self.clips.len() > 1 {
+                self.clips.pop();
+            }
+        }
+
+        fn push_layer(&mut self, mode: CompositeMode) {
+            let (w, h) = self.size();
+            if let Some(p) = Pixmap::new(w, h) {
+                self.layers.push((p, blend_mode(mode)));
+            }
+        }
+
+        fn pop_layer(&mut self) {
+            if self.layers.len() < 2 {
+                return;
+            }
+            let (layer, mode) = self.layers.pop().unwrap();
+            let paint = PixmapPaint { blend_mode: mode, ..PixmapPaint::default() };
+            self.layers.last_mut().unwrap().0.draw_pixmap(0, 0, layer.as_ref(), &paint, Transform::identity(), None);
+        }
+
+        fn push_transform(&mut self, t: ttf_parser::Transform) {
+            let current = self.transform();
+            self.transforms.push(current.pre_concat(Transform::from_row(t.a, t.b, t.c, t.d, t.e, t.f)));
+        }
+
+        fn pop_transform(&mut self) {
+            if self.transforms.len() > 1 {
+                self.transforms.pop();
+            }
+        }
+    }
+
+    fn color(c: RgbaColor) -> tiny_skia::Color {
+        tiny_skia::Color::from_rgba8(c.red, c.green, c.blue, c.alpha)
+    }
+
+    fn spread(extend: GradientExtend) -> SpreadMode {
+        match extend {
+            GradientExtend::Pad => SpreadMode::Pad,
+            GradientExtend::Repeat => SpreadMode::Repeat,
+            GradientExtend::Reflect => SpreadMode::Reflect,
+        }
+    }
+
+    fn stops(iter: impl Iterator<Item = colr::ColorStop>) -> Vec<GradientStop> {
+        let mut stops: Vec<colr::ColorStop> = iter.collect();
+        stops.sort_by(|a, b| a.stop_offset.total_cmp(&b.stop_offset));
+        stops.into_iter().map(|s| GradientStop::new(s.stop_offset, color(s.color))).collect()
+    }
+
+    /// COLR 绘制方式转为 tiny-skia shader；双圆径向渐变近似为焦点渐变，扫描渐变退化为平均色
+    fn shader(paint: &colr::Paint, transform: Transform) -> Option<Shader<'static>> {
+        match paint {
+            colr::Paint::Solid(c) => Some(Shader::SolidColor(color(*c))),
+            colr::Paint::LinearGradient(g) => LinearGradient::new(
+                Point::from_xy(g.x0, g.y0),
+                Point::from_xy(g.x1, g.y1),
+                stops(g.stops(0, &[])),
+                spread(g.extend),
+                transform,
+            ),
+            colr::Paint::RadialGradient(g) => RadialGradient::new(
+                Point::from_xy(g.x0, g.y0),
+                Point::from_xy(g.x1, g.y1),
+                g.r1.max(g.r0),
+                stops(g.stops(0, &[])),
+                spread(g.extend),
+                transform,
+            ),
+            colr::Paint::SweepGradient(g) => {
+                let colors: Vec<RgbaColor> = g.stops(0, &[]).map(|s| s.color).collect();
+                let n = colors.len().max(1) as u32;
+                let avg = |f: fn(&RgbaColor) -> u8| (colors.iter().map(|c| f(c) as u32).sum::<u32>() / n) as u8;
+                Some(Shader::SolidColor(color(RgbaColor::new(avg(|c| c.red), avg(|c| c.green), avg(|c| c.blue), avg(|c| c.alpha)))))
+            }
+        }
+    }
+
+    fn blend_mode(mode: CompositeMode) -> BlendMode {
+        match mode {
+            CompositeMode::Clear => BlendMode::Clear,
+            CompositeMode::Source => BlendMode::Source,
+            CompositeMode::Destination => BlendMode::Destination,
+            CompositeMode::SourceOver => BlendMode::SourceOver,
+            CompositeMode::DestinationOver => BlendMode::DestinationOver,
+            CompositeMode::SourceIn => BlendMode::SourceIn,
+            CompositeMode::DestinationIn => BlendMode::DestinationIn,
+            CompositeMode::SourceOut => BlendMode::SourceOut,
+            CompositeMode::DestinationOut => BlendMode::DestinationOut,
+            CompositeMode::SourceAtop => BlendMode::SourceAtop,
+            CompositeMode::DestinationAtop => BlendMode::DestinationAtop,
+            CompositeMode::Xor => BlendMode::Xor,
+            CompositeMode::Plus => BlendMode::Plus,
+            CompositeMode::Screen => BlendMode::Screen,
+            CompositeMode::Overlay => BlendMode::Overlay,
+            CompositeMode::Darken => BlendMode::Darken,
+            CompositeMode::Lighten => BlendMode::Lighten,
+            CompositeMode::ColorDodge => BlendMode::ColorDodge,
+            CompositeMode::ColorBurn => BlendMode::ColorBurn,
+            CompositeMode::HardLight => BlendMode::HardLight,
+            CompositeMode::SoftLight => BlendMode::SoftLight,
+            CompositeMode::Difference => BlendMode::Difference,
+            CompositeMode::Exclusion => BlendMode::Exclusion,
+            CompositeMode::Multiply => BlendMode::Multiply,
+            CompositeMode::Hue => BlendMode::Hue,
+            CompositeMode::Saturation => BlendMode::Saturation,
+            CompositeMode::Color => BlendMode::Color,
+            CompositeMode::Luminosity => BlendMode::Luminosity,
+        }
+    }
+
+    struct Builder(PathBuilder);
+
+    impl ttf_parser::OutlineBuilder for Builder {
+        fn move_to(&mut self, x: f32, y: f32) {
+            self.0.move_to(x, y);
+        }
+        fn line_to(&mut self, x: f32, y: f32) {
+            self.0.line_to(x, y);
+        }
+        fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+            self.0.quad_to(x1, y1, x, y);
+        }
+        fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+            self.0.cubic_to(x1, y1, x2, y2, x, y);
+        }
+        fn close(&mut self) {
+            self.0.close();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "render"))]
+mod tests {
+    use super::scale_png;
+    use tiny_skia::{Color, Pixmap};
+
+    #[test]
+    fn bitmap_strike_is_scaled_to_requested_size() {
+        let mut src = Pixmap::new(136, 128).unwrap();
+        src.fill(Color::from_rgba8(255, 0, 0, 255));
+        let png = scale_png(&src.encode_png().unwrap(), 72).unwrap();
+        let out = Pixmap::decode_png(&png).unwrap();
+        assert_eq!((out.width(), out.height()), (72, 72));
+        // 等比缩放，上下留白
+        assert_eq!(out.pixel(36, 0).unwrap().alpha(), 0);
+        assert_eq!(out.pixel(36, 36).unwrap().alpha(), 255);
+    }
+}
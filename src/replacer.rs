@@ -1,80 +1,197 @@
-use std::env;
 use std::fs::File;
 use std::io::{Read, Seek, Write};
 use zip::{ZipArchive, ZipWriter, write::FileOptions};
-use unicode_segmentation::UnicodeSegmentation;
-use emojis;
 use quick_xml::Reader;
 use quick_xml::events::{Event, BytesStart};
 use quick_xml::Writer;
 use std::io::Cursor;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cache;
+use crate::coverage::{font_face_urls, FontCoverage};
+use crate::detect::DetectionPolicy;
+use crate::naming;
+use crate::provider::{acquire_images, acquire_images_from, ImageFallback, ImageSource, OptionsSource, Provider, ResolvedImage};
+use crate::rewrite::{has_regional_indicator_letters, rewrite_emoji, DEFAULT_IMG_STYLE};
+use crate::scan::{scan, scan_epub};
+use crate::render::EmojiFont;
+use crate::EmojiSourceMode;
+
+/// 替换选项：图片来源、尺寸、缓存目录、在线/离线
+#[derive(Clone, Debug)]
+pub struct ReplaceOptions {
+    pub provider: Provider,
+    pub size: u32,
+    /// 图片集版本（git 标签或分支），None 时使用 [`Provider::default_version`]
+    pub provider_version: Option<String>,
+    /// 缓存根目录，None 时使用 exe 所在目录下的 emoji_img
+    pub cache_dir: Option<PathBuf>,
+    /// Local 时只使用缓存中已有的图片，不联网下载
+    pub source_mode: EmojiSourceMode,
+    /// 哪些字符算作 emoji
+    pub detection: DetectionPolicy,
+    /// 设置后从该彩色字体生成图片，代替 provider 图片集
+    pub emoji_font: Option<EmojiFont>,
+    /// 插入的 img 标签的 style 属性
+    pub img_style: String,
+}
+
+impl Default for ReplaceOptions {
+    fn default() -> Self {
+        ReplaceOptions {
+            provider: Provider::Twemoji,
+            size: Provider::Twemoji.default_size(),
+            provider_version: None,
+            cache_dir: None,
+            source_mode: EmojiSourceMode::Online,
+            detection: DetectionPolicy::default(),
+            emoji_font: None,
+            img_style: DEFAULT_IMG_STYLE.to_string(),
+        }
+    }
+}
+
+impl ReplaceOptions {
+    /// 当前图片集/尺寸对应的本地图片目录，使用字体时为 `font_{字体名}_{size}`
+    pub fn image_dir(&self) -> PathBuf {
+        let cache_dir = self.cache_dir.clone().unwrap_or_else(cache::default_cache_dir);
+        match &self.emoji_font {
+            Some(font) => cache_dir.join(format!("font_{}_{}", font.name(), self.size)),
+            None => self.provider_image_dir(),
+        }
+    }
+
+    /// 图片集的缓存目录，不受 emoji_font 影响
+    pub fn provider_image_dir(&self) -> PathBuf {
+        let cache_dir = self.cache_dir.clone().unwrap_or_else(cache::default_cache_dir);
+        cache::image_dir(&cache_dir, self.provider, self.size, self.provider_version.as_deref())
+    }
+
+    /// 实际使用的图片集版本
+    pub fn image_version(&self) -> &str {
+        self.provider_version.as_deref().unwrap_or(self.provider.default_version())
+    }
+}
+
+/// 处理结果汇总
+#[derive(Clone, Debug, Default)]
+pub struct ReplaceReport {
+    /// code -> 替换次数，不含缺少图片、保留文字的 emoji
+    pub counts: HashMap<String, usize>,
+    /// code -> 实际使用的图片及回退方式
+    pub images: HashMap<String, ResolvedImage>,
+}
 
 /// 替换 epub 文件中的 emoji 为图片
 pub fn replace_emoji_in_epub_impl(
     input_path: &str,
     output_path: &str,
 ) -> Result<(), String> {
-    let mut global_counts: HashMap<String, usize> = HashMap::new();
-    use std::collections::HashSet;
-    println!("[epub_emoji_x] 打开输入文件: {}", input_path);
+    replace_emoji_in_epub_with_options(input_path, output_path, &ReplaceOptions::default()).map(|_| ())
+}
+
+/// 替换 epub 文件中的 emoji 为图片（指定图片来源等选项）
+pub fn replace_emoji_in_epub_with_options(
+    input_path: &str,
+    output_path: &str,
+    opts: &ReplaceOptions,
+) -> Result<ReplaceReport, String> {
+    crate::log_info!("打开输入文件: {}", input_path);
     let input_file = File::open(input_path).map_err(|e| format!("打开输入文件失败: {}", e))?;
-    let mut zip = ZipArchive::new(input_file).map_err(|e| format!("解析epub为zip失败: {}", e))?;
-    println!("[epub_emoji_x] 成功打开epub并解析zip");
+    // 处理成功后才创建输出文件
+    let mut output = Cursor::new(Vec::new());
+    let report = replace_emoji_in_epub_stream(input_file, &mut output, opts)?;
+    crate::log_info!("写入输出文件: {}", output_path);
+    std::fs::write(output_path, output.into_inner()).map_err(|e| format!("创建输出文件失败: {}", e))?;
+    Ok(report)
+}
+
+/// 内存中的 epub 替换：输入 epub 字节，返回新 epub 字节
+pub fn replace_emoji_in_epub_bytes(input: &[u8], opts: &ReplaceOptions) -> Result<Vec<u8>, String> {
+    let mut output = Cursor::new(Vec::new());
+    replace_emoji_in_epub_stream(Cursor::new(input), &mut output, opts)?;
+    Ok(output.into_inner())
+}
+
+/// 从任意 `Read + Seek` 读取 epub，把结果写入任意 `Write + Seek`
+pub fn replace_emoji_in_epub_stream<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    opts: &ReplaceOptions,
+) -> Result<ReplaceReport, String> {
+    replace_emoji_in_epub_with_source(input, output, opts, &mut OptionsSource(opts))
+}
+
+/// 同 [`replace_emoji_in_epub_stream`]，图片从 source 取得（opts 只提供识别策略）
+pub fn replace_emoji_in_epub_with_source<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    opts: &ReplaceOptions,
+    source: &mut dyn ImageSource,
+) -> Result<ReplaceReport, String> {
+    replace_emoji_in_epub_controlled(input, output, opts, source, &mut ProcessControl::default())
+}
+
+/// epub 中各 xhtml/html 出现的 emoji 的规范 code（去重），可用于预先准备图片
+pub fn collect_emoji_codes<R: Read + Seek>(input: R, policy: &DetectionPolicy) -> Result<std::collections::BTreeSet<String>, String> {
+    let files = scan_epub(input, policy)?;
+    Ok(files.into_iter().flat_map(|(_, matches)| matches.into_iter().map(|m| m.code)).collect())
+}
+
+/// 取消时返回的错误信息
+pub const CANCELLED: &str = "已取消";
+
+/// 处理进度，每处理完一个 xhtml/html 文件报告一次
+#[derive(Clone, Debug)]
+pub struct Progress<'a> {
+    pub file: &'a str,
+    pub files_done: usize,
+    pub files_total: usize,
+    /// 到目前为止替换的 emoji 数
+    pub emoji_count: usize,
+}
+
+/// 进度回调与取消标志
+#[derive(Default)]
+pub struct ProcessControl<'a> {
+    pub on_progress: Option<&'a mut dyn FnMut(&Progress)>,
+    /// 置为 true 后在处理下一个文件前停止，返回 [`CANCELLED`]
+    pub cancel: Option<&'a AtomicBool>,
+}
+
+impl ProcessControl<'_> {
+    fn cancelled(&self) -> bool {
+        self.cancel.map(|c| c.load(Ordering::Relaxed)).unwrap_or(false)
+    }
+}
+
+/// 同 [`replace_emoji_in_epub_with_source`]，带进度回调与取消
+pub fn replace_emoji_in_epub_controlled<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    opts: &ReplaceOptions,
+    source: &mut dyn ImageSource,
+    control: &mut ProcessControl,
+) -> Result<ReplaceReport, String> {
+    let mut global_counts: HashMap<String, usize> = HashMap::new();
+    let mut resolved: HashMap<String, ResolvedImage> = HashMap::new();
+    let mut zip = ZipArchive::new(input).map_err(|e| format!("解析epub为zip失败: {}", e))?;
+    crate::log_debug!("成功打开epub并解析zip");
     let mut buffer_map = vec![];
     let mut emoji_imgs = HashSet::new();
-    let mut opf_path = None;
-    let mut opf_content = None;
-    // 查找 opf 路径
-    let mut opf_dir = String::new();
-    let mut nav_files = std::collections::HashSet::new();
-    if let Some(path) = find_opf_path_from_container(&mut zip) {
-        if let Ok(mut opf_file) = zip.by_name(&path) {
-            let mut content = String::new();
-            opf_file.read_to_string(&mut content).ok();
-            // 修正：解析 nav 文件名，拼接并规范化为 zip 内路径
-            {
-                let opf_dir_path = std::path::Path::new(&path).parent().unwrap_or_else(|| std::path::Path::new(""));
-                let mut reader = Reader::from_str(&content);
-                reader.trim_text(true);
-                let mut buf: Vec<u8> = Vec::new();
-                loop {
-                    match reader.read_event() {
-                        Ok(Event::Empty(ref e)) => {
-                            let mut is_nav = false;
-                            let mut href_val = None;
-                            for attr in e.attributes().flatten() {
-                                if attr.key.as_ref() == b"properties" && attr.value.as_ref() == b"nav" {
-                                    is_nav = true;
-                                }
-                                if attr.key.as_ref() == b"href" {
-                                    href_val = Some(String::from_utf8_lossy(&attr.value).to_string());
-                                }
-                            }
-                            if is_nav {
-                                if let Some(href) = href_val {
-                                    // 拼接成 zip 内部的完整路径并规范化
-                                    let nav_path = opf_dir_path.join(href).components().collect::<PathBuf>();
-                                    let nav_path_str = nav_path.to_string_lossy().replace("\\", "/");
-                                    nav_files.insert(nav_path_str);
-                                }
-                            }
-                        }
-                        Ok(Event::Eof) => break,
-                        _ => {}
-                    }
-                    buf.clear();
-                }
-            }
-            opf_content = Some(content);
+    let BookLayout { opf_path, opf_content, opf_dir, nav_files } = BookLayout::read(&mut zip);
+    // 使用书内 @font-face 引用的字体判断覆盖
+    let book_opts;
+    let opts = match book_policy(&mut zip, &opts.detection) {
+        Cow::Borrowed(_) => opts,
+        Cow::Owned(detection) => {
+            book_opts = ReplaceOptions { detection, ..opts.clone() };
+            &book_opts
         }
-        opf_dir = std::path::Path::new(&path)
-            .parent()
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_else(|| "".to_string());
-        opf_path = Some(path);
-    }
+    };
     // emoji_img 目录放在 opf 同级目录
     let emoji_dir = if opf_dir.is_empty() {
         "emoji_img".to_string()
@@ -82,6 +199,8 @@ pub fn replace_emoji_in_epub_impl(
         format!("{}/emoji_img", opf_dir)
     };
 
+    let files_total = zip.file_names().filter(|n| is_content_file(n, &nav_files)).count();
+    let mut files_done = 0;
     // 遍历所有文件，处理 xhtml/html
     for i in 0..zip.len() {
         let mut file = zip.by_index(i).map_err(|e| format!("读取zip第{}个文件失败: {}", i, e))?;
@@ -92,26 +211,22 @@ pub fn replace_emoji_in_epub_impl(
         let name_normalized = name.replace("\\", "/");
         if name.ends_with(".xhtml") || name.ends_with(".html") {
             if nav_files.contains(&name_normalized) {
-                println!("[epub_emoji_x] 跳过nav文件: {}", name);
+                crate::log_debug!("跳过nav文件: {}", name);
                 buffer_map.push((name.clone(), buf.clone()));
                 continue;
             }
+            if control.cancelled() {
+                crate::log_info!("已取消");
+                return Err(CANCELLED.to_string());
+            }
             if let Ok(orig_str) = String::from_utf8(buf.clone()) {
-                // 先局部统计本文件的 emoji 数量
+                // 先局部统计本文件的 emoji 数量（含字符引用形式）
                 let mut counts: HashMap<String, usize> = HashMap::new();
-                for g in orig_str.graphemes(true) {
-                    if is_emoji_grapheme(g) {
-                        let code = g.chars()
-                            .map(|c| format!("{:x}", c as u32)) // 改为小写
-                            .collect::<Vec<_>>()
-                            .join("-");
-                        let code = code.to_lowercase(); // 统一小写
-                        *counts.entry(code.clone()).or_insert(0) += 1;
-                        *global_counts.entry(code).or_insert(0) += 1;
-                    }
+                for m in scan(&orig_str, &opts.detection) {
+                    *counts.entry(m.code).or_insert(0) += 1;
                 }
-                // 仅当需要替换时，才计算路径 & 执行替换
-                if !counts.is_empty() {
+                // 仅当需要替换（含显示为字母的区域指示符）时，才计算路径 & 执行替换
+                if !counts.is_empty() || has_regional_indicator_letters(&orig_str) {
                     // 先算出这次文件的 img_rel
                     let xhtml_dir = Path::new(&name)
                         .parent()
@@ -129,31 +244,43 @@ pub fn replace_emoji_in_epub_impl(
                         .to_string()
                     };
         
-                    // 打印日志 & 记录要插入的图片
-                    let total_file: usize = counts.values().sum();
-                    let detail_file = counts.iter()
-                        .map(|(code, &n)| format!("{}×{}", code, n))
-                        .collect::<Vec<_>>()
-                        .join(", ");
-                    println!(
-                        "[epub_emoji_x] 文件={}，共替换 {} 个emoji： {}",
-                        name, total_file, detail_file
-                    );
-                    for code in counts.keys() {
-                        emoji_imgs.insert(format!("{}.png", code.to_lowercase())); // 统一小写
+                    // 先取得图片；缺少图片的保留文字，不计入替换数
+                    acquire_images_from(source, counts.keys(), &mut resolved);
+                    counts.retain(|code, _| {
+                        resolved.get(code).map(|image| image.fallback != ImageFallback::Missing).unwrap_or(true)
+                    });
+                    for (code, &n) in &counts {
+                        *global_counts.entry(code.clone()).or_insert(0) += n;
+                    }
+                    // 打印日志 & 改写并写入 buffer_map
+                    if !counts.is_empty() {
+                        let total_file: usize = counts.values().sum();
+                        let detail_file = counts.iter()
+                            .map(|(code, &n)| format!("{}×{}", code, n))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        crate::log_info!(
+                            "文件={}，共替换 {} 个emoji： {}",
+                            name, total_file, detail_file
+                        );
                     }
-                    // 真正替换并写入 buffer_map
-                    let replaced = replace_emoji_in_xhtml_with_imgdir(&orig_str, &img_rel);
-                    buffer_map.push((name.clone(), replaced.into_bytes()));
+                    let rewritten = rewrite_emoji(&orig_str, &img_rel, &opts.img_style, &opts.detection, &resolved);
+                    emoji_imgs.extend(rewritten.assets.iter().map(|c| naming::book_filename(c)));
+                    buffer_map.push((name.clone(), rewritten.content.into_bytes()));
                 } else {
                     // 如果没有 emoji，直接原样写回
                     buffer_map.push((name.clone(), buf.clone()));
                 }
             } else {
                 // UTF-8 解码失败，也原样写回
-                println!("[epub_emoji_x] 文件utf8解码失败: {}", name);
+                crate::log_info!("文件utf8解码失败: {}", name);
                 buffer_map.push((name.clone(), buf.clone()));
             }
+            files_done += 1;
+            if let Some(on_progress) = control.on_progress.as_mut() {
+                let emoji_count = global_counts.values().sum();
+                on_progress(&Progress { file: &name, files_done, files_total, emoji_count });
+            }
             continue;
         }
         // 非 .xhtml/.html 文件，原样写回
@@ -169,155 +296,222 @@ pub fn replace_emoji_in_epub_impl(
             .map(|(code, &n)| format!("{}×{}", code, n))
             .collect::<Vec<_>>()
             .join(", ");
-        println!(
-            "[epub_emoji_x] 共替换 {} 个emoji，种类数 {}，详细：{}",
+        crate::log_info!(
+            "共替换 {} 个emoji，种类数 {}，详细：{}",
             total_all, distinct_count, detail_all
         );
     }
+    // 打印使用了回退的 emoji
+    for (code, image) in &resolved {
+        if image.fallback == ImageFallback::Missing {
+            crate::log_info!("缺少图片，保留文字: {}", code);
+        } else if image.fallback != ImageFallback::Exact {
+            crate::log_info!("图片回退: {} -> {:?} {}", code, image.fallback, image.codes.join(" + "));
+        }
+    }
     // 更新 opf 清单
     if let (Some(path), Some(content)) = (opf_path, opf_content) {
-        println!("[epub_emoji_x] 更新opf清单: {}", path);
+        crate::log_debug!("更新opf清单: {}", path);
         let new_opf = update_opf_manifest(&content, &emoji_imgs, &emoji_dir);
         buffer_map.retain(|(n, _)| n != &path);
         buffer_map.push((path, new_opf.into_bytes()));
     }
     // 写回新 epub
-    println!("[epub_emoji_x] 开始写回epub: {}", output_path);
-    let out_file = File::create(output_path).map_err(|e| format!("创建输出文件失败: {}", e))?;
-    let mut writer = ZipWriter::new(out_file);
+    crate::log_debug!("开始写回epub");
+    let mut writer = ZipWriter::new(output);
     let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
     for (name, data) in &buffer_map {
         writer.start_file(name, options).map_err(|e| format!("写入zip文件失败: {}", e))?;
         writer.write_all(data).map_err(|e| format!("写入zip内容失败: {}", e))?;
     }
     // 插入 emoji 图片资源
-    let exe_dir = env::current_exe().ok().and_then(|p| p.parent().map(|d| d.to_path_buf())).unwrap_or_else(|| std::path::PathBuf::from("."));
     for filename in emoji_imgs {
         let filename = filename.to_lowercase(); // 统一小写
-        let local_img_path = exe_dir.join("emoji_img").join(&filename);
-        println!("[epub_emoji_x] 插入emoji图片文件: {}", local_img_path.display());
-        if let Ok(mut img_file) = File::open(&local_img_path) {
-            let mut img_data = Vec::new();
-            img_file.read_to_end(&mut img_data).map_err(|e| e.to_string())?;
+        let code = naming::code_from_book_filename(&filename).unwrap_or(&filename);
+        crate::log_debug!("插入emoji图片文件: {}", filename);
+        if let Some(img_data) = source.load(code) {
             writer.start_file(format!("{}/{}", emoji_dir, filename), options).map_err(|e| e.to_string())?;
             writer.write_all(&img_data).map_err(|e| e.to_string())?;
         } else {
-            println!("[epub_emoji_x] emoji图片文件不存在: {}", local_img_path.display());
+            crate::log_info!("emoji图片文件不存在: {}", filename);
         }
     }
     writer.finish().map_err(|e| format!("zip写入完成失败: {}", e))?;
-    println!("[epub_emoji_x] 处理完成");
-    Ok(())
+    crate::log_info!("处理完成");
+    Ok(ReplaceReport { counts: global_counts, images: resolved })
 }
 
-fn find_opf_path_from_container<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Option<String> {
-    let mut container_xml = String::new();
-    if let Ok(mut file) = zip.by_name("META-INF/container.xml") {
-        file.read_to_string(&mut container_xml).ok()?;
-        let mut reader = Reader::from_str(&container_xml);
-        reader.trim_text(true);
-        let mut buf: Vec<u8> = Vec::new();
-        while let Ok(event) = reader.read_event() {
-            match event {
-                Event::Empty(ref e) | Event::Start(ref e) => {
-                    if e.name().as_ref() == b"rootfile" {
-                        for attr in e.attributes().flatten() {
-                            if attr.key.as_ref() == b"full-path" {
-                                return Some(String::from_utf8_lossy(&attr.value).to_string());
+/// epub 的 opf 位置与 nav 文件
+pub(crate) struct BookLayout {
+    pub(crate) opf_path: Option<String>,
+    pub(crate) opf_content: Option<String>,
+    /// opf 所在目录，在根目录时为空
+    pub(crate) opf_dir: String,
+    /// nav 文件的 zip 内路径，不替换其中的 emoji
+    pub(crate) nav_files: HashSet<String>,
+}
+
+impl BookLayout {
+    pub(crate) fn read<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Self {
+        let mut opf_path = None;
+        let mut opf_content = None;
+        // 查找 opf 路径
+        let mut opf_dir = String::new();
+        let mut nav_files = HashSet::new();
+        if let Some(path) = find_opf_path_from_container(zip) {
+            if let Ok(mut opf_file) = zip.by_name(&path) {
+                let mut content = String::new();
+                opf_file.read_to_string(&mut content).ok();
+                // 修正：解析 nav 文件名，拼接并规范化为 zip 内路径
+                {
+                    let opf_dir_path = std::path::Path::new(&path).parent().unwrap_or_else(|| std::path::Path::new(""));
+                    let mut reader = Reader::from_str(&content);
+                    reader.trim_text(true);
+                    let mut buf: Vec<u8> = Vec::new();
+                    loop {
+                        match reader.read_event() {
+                            Ok(Event::Empty(ref e)) => {
+                                let mut is_nav = false;
+                                let mut href_val = None;
+                                for attr in e.attributes().flatten() {
+                                    if attr.key.as_ref() == b"properties" && attr.value.as_ref() == b"nav" {
+                                        is_nav = true;
+                                    }
+                                    if attr.key.as_ref() == b"href" {
+                                        href_val = Some(String::from_utf8_lossy(&attr.value).to_string());
+                                    }
+                                }
+                                if is_nav {
+                                    if let Some(href) = href_val {
+                                        // 拼接成 zip 内部的完整路径并规范化
+                                        let nav_path = opf_dir_path.join(href).components().collect::<PathBuf>();
+                                        let nav_path_str = nav_path.to_string_lossy().replace("\\", "/");
+                                        nav_files.insert(nav_path_str);
+                                    }
+                                }
                             }
+                            Ok(Event::Eof) => break,
+                            _ => {}
                         }
+                        buf.clear();
                     }
                 }
-                Event::Eof => break,
-                _ => {}
+                opf_content = Some(content);
             }
-            buf.clear();
+            opf_dir = std::path::Path::new(&path)
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            opf_path = Some(path);
         }
+        BookLayout { opf_path, opf_content, opf_dir, nav_files }
     }
-    None
 }
 
-// 新的 replace_emoji_in_xhtml_with_imgdir
-fn download_and_save(url: &str, filename: &str) -> Result<(), String> {
-    use std::path::Path;
-    let filename = filename.to_lowercase(); // 统一小写
-    let exe_dir = std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.to_path_buf())).unwrap_or_else(|| Path::new(".").to_path_buf());
-    let abs_path = exe_dir.join("emoji_img").join(&filename);
-    // 优先尝试不带 -fe0f 的图片
-    if let Some(stripped) = filename.strip_suffix("-fe0f.png") {
-        let fallback = exe_dir.join("emoji_img").join(format!("{}.png", stripped));
-        if fallback.exists() {
-            std::fs::copy(&fallback, &abs_path).map_err(|e| e.to_string())?;
-            println!("[epub_emoji_x] 使用无 -fe0f 变体图片: {} -> {}", fallback.display(), abs_path.display());
-            return Ok(());
-        }
+/// 是否为替换 emoji 的 xhtml/html（跳过 nav 文件，路径规范化后比较）
+pub(crate) fn is_content_file(name: &str, nav_files: &HashSet<String>) -> bool {
+    (name.ends_with(".xhtml") || name.ends_with(".html")) && !nav_files.contains(&name.replace("\\", "/"))
+}
+
+/// 书的识别策略：启用 embedded_fonts 时加入书内 @font-face 引用的字体
+pub(crate) fn book_policy<'p, R: Read + Seek>(zip: &mut ZipArchive<R>, policy: &'p DetectionPolicy) -> Cow<'p, DetectionPolicy> {
+    if !policy.embedded_fonts {
+        return Cow::Borrowed(policy);
     }
-    println!("[epub_emoji_x] 下载emoji图片: {} -> {}", url, abs_path.display());
-    if let Ok(resp) = reqwest::blocking::get(url) {
-        if resp.status().is_success() {
-            let bytes = resp.bytes().map_err(|e| e.to_string())?;
-            if let Some(parent) = abs_path.parent() {
-                if !parent.exists() {
-                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-                }
+    let mut policy = policy.clone();
+    load_embedded_fonts(zip, &mut policy.font_coverage);
+    Cow::Owned(policy)
+}
+
+/// 把 css/xhtml 中 @font-face 引用的 TTF/OTF 字体加入 coverage
+fn load_embedded_fonts<R: Read + Seek>(zip: &mut ZipArchive<R>, coverage: &mut FontCoverage) {
+    let names: Vec<String> = zip.file_names().map(|n| n.to_string()).collect();
+    let mut font_paths = std::collections::BTreeSet::new();
+    for name in &names {
+        if !(name.ends_with(".css") || name.ends_with(".xhtml") || name.ends_with(".html")) {
+            continue;
+        }
+        let mut content = String::new();
+        if let Ok(mut file) = zip.by_name(name) {
+            if file.read_to_string(&mut content).is_err() {
+                continue;
             }
-            let mut file = std::fs::File::create(&abs_path).map_err(|e| e.to_string())?;
-            file.write_all(&bytes).map_err(|e| e.to_string())?;
-            return Ok(());
+        }
+        let dir = Path::new(name).parent().unwrap_or_else(|| Path::new(""));
+        for url in font_face_urls(&content) {
+            font_paths.insert(normalize_zip_path(&dir.join(url.split(['#', '?']).next().unwrap_or(&url))));
         }
     }
-    if let Some(stripped) = filename.strip_suffix("-fe0f.png") {
-        let fallback_url = emoji_to_url_base(stripped);
-        let fallback_path = exe_dir.join("emoji_img").join(format!("{}.png", stripped));
-        println!("[epub_emoji_x] 尝试下载无 -fe0f 变体图片: {} -> {}", fallback_url, fallback_path.display());
-        if let Ok(resp) = reqwest::blocking::get(&fallback_url) {
-            if resp.status().is_success() {
-                let bytes = resp.bytes().map_err(|e| e.to_string())?;
-                if let Some(parent) = fallback_path.parent() {
-                    if !parent.exists() {
-                        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-                    }
+    for path in font_paths {
+        let mut data = Vec::new();
+        match zip.by_name(&path) {
+            Ok(mut file) => {
+                if file.read_to_end(&mut data).is_err() {
+                    continue;
                 }
-                let mut file = std::fs::File::create(&fallback_path).map_err(|e| e.to_string())?;
-                file.write_all(&bytes).map_err(|e| e.to_string())?;
-                std::fs::copy(&fallback_path, &abs_path).map_err(|e| e.to_string())?;
-                println!("[epub_emoji_x] 下载并使用无 -fe0f 变体图片: {} -> {}", fallback_url, abs_path.display());
-                return Ok(());
             }
+            Err(_) => continue,
+        }
+        match coverage.add_font_data(&path, data) {
+            Ok(_) => crate::log_debug!("使用书内字体判断覆盖: {}", path),
+            Err(e) => crate::log_info!("跳过书内字体（可能是 woff 或已混淆）: {}", e),
         }
     }
-    Err(format!("下载失败: {}", url))
 }
 
-fn emoji_to_url(emoji: &str) -> String {
-    let codepoints: Vec<String> = emoji.chars().map(|c| format!("{:x}", c as u32)).collect();
-    format!("https://gcore.jsdelivr.net/gh/twitter/twemoji@14.0.2/assets/72x72/{}.png", codepoints.join("-"))
-}
-
-fn emoji_to_url_base(codepoint: &str) -> String {
-    format!("https://gcore.jsdelivr.net/gh/twitter/twemoji@14.0.2/assets/72x72/{}.png", codepoint)
+/// 去掉 `.`/`..`，返回 zip 内使用 `/` 分隔的路径
+fn normalize_zip_path(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for comp in path.components() {
+        match comp {
+            std::path::Component::ParentDir => {
+                parts.pop();
+            }
+            std::path::Component::Normal(p) => parts.push(p.to_string_lossy().to_string()),
+            _ => {}
+        }
+    }
+    parts.join("/")
 }
 
-pub fn replace_emoji_in_xhtml_with_imgdir(xhtml: &str, imgdir: &str) -> String {
-    let mut result = String::new();
-    let exe_dir = std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.to_path_buf())).unwrap_or_else(|| std::path::PathBuf::from("."));
-    let imgdir = imgdir.replace("\\", "/");
-    for g in xhtml.graphemes(true) {
-        if is_emoji_grapheme(g) {
-            let codepoints: Vec<String> = g.chars().map(|c| format!("{:x}", c as u32)).collect();
-            let filename = format!("{}.png", codepoints.join("-").to_lowercase()); // 统一小写
-            let abs_path = exe_dir.join("emoji_img").join(&filename);
-            if !abs_path.exists() {
-                let url = emoji_to_url(g);
-                let _ = download_and_save(&url, &filename);
+pub(crate) fn find_opf_path_from_container<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Option<String> {
+    let mut container_xml = String::new();
+    if let Ok(mut file) = zip.by_name("META-INF/container.xml") {
+        file.read_to_string(&mut container_xml).ok()?;
+        let mut reader = Reader::from_str(&container_xml);
+        reader.trim_text(true);
+        let mut buf: Vec<u8> = Vec::new();
+        while let Ok(event) = reader.read_event() {
+            match event {
+                Event::Empty(ref e) | Event::Start(ref e) if e.name().as_ref() == b"rootfile" => {
+                    for attr in e.attributes().flatten() {
+                        if attr.key.as_ref() == b"full-path" {
+                            return Some(String::from_utf8_lossy(&attr.value).to_string());
+                        }
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
             }
-            let img_tag = format!("\n<img alt=\"{}\" src=\"{}/{}\" style=\"height:1.3em\"/>\n", g, imgdir, filename);
-            result.push_str(&img_tag);
-        } else {
-            result.push_str(g);
+            buf.clear();
         }
     }
-    result
+    None
+}
+
+/// 替换 xhtml 中的 emoji 为 img 标签，缺失的图片下载到默认缓存
+pub fn replace_emoji_in_xhtml_with_imgdir(xhtml: &str, imgdir: &str) -> String {
+    replace_emoji_in_xhtml_with_options(xhtml, imgdir, &ReplaceOptions::default())
+}
+
+/// 替换 xhtml 中的 emoji 为 img 标签，缺失的图片按 opts 下载到缓存
+///
+/// 会访问缓存目录与网络；只改写标记可使用 [`crate::rewrite::rewrite_emoji`]。
+pub fn replace_emoji_in_xhtml_with_options(xhtml: &str, imgdir: &str, opts: &ReplaceOptions) -> String {
+    let mut resolved = HashMap::new();
+    let codes: Vec<String> = scan(xhtml, &opts.detection).map(|m| m.code).collect();
+    acquire_images(opts, &codes, &mut resolved);
+    rewrite_emoji(xhtml, imgdir, &opts.img_style, &opts.detection, &resolved).content
 }
 
 // update_opf_manifest: href 只写 emoji_img/xxx.png，且格式化输出
@@ -379,11 +573,28 @@ fn update_opf_manifest(opf_content: &str, emoji_imgs: &std::collections::HashSet
     String::from_utf8(writer.into_inner().into_inner()).unwrap_or_else(|_| opf_content.to_string())
 }
 
-fn is_emoji_grapheme(g: &str) -> bool {
-    emojis::get(g).is_some()
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::CallbackSource;
+    use crate::testutil;
+    use std::io::Cursor;
 
-pub mod replacer {
-    pub use super::replace_emoji_in_epub_impl;
-    pub use super::replace_emoji_in_xhtml_with_imgdir;
-}
\ No newline at end of file
+    #[test]
+    fn missing_images_are_not_counted_as_replaced() {
+        let input = testutil::epub("<p>😀 😀 🦖</p>");
+        let mut source = CallbackSource::new(|code: &str| (code == "1f600").then(|| b"png".to_vec()));
+        let mut progress = Vec::new();
+        let mut on_progress = |p: &Progress| progress.push(p.emoji_count);
+        let mut control = ProcessControl { on_progress: Some(&mut on_progress), cancel: None };
+        let mut output = Cursor::new(Vec::new());
+        let report =
+            replace_emoji_in_epub_controlled(Cursor::new(input), &mut output, &ReplaceOptions::default(), &mut source, &mut control)
+                .unwrap();
+        assert_eq!(report.counts, HashMap::from([("1f600".to_string(), 2)]));
+        assert_eq!(report.images["1f996"].fallback, ImageFallback::Missing);
+        assert_eq!(progress, [2]);
+        let chapter = testutil::read_entry(&output.into_inner(), "OEBPS/Text/c1.xhtml").unwrap();
+        assert!(chapter.contains("🦖"));
+    }
+}
@@ -0,0 +1,234 @@
+//! 还原：把本工具插入的 emoji 图片换回文字
+//!
+//! 只识别 src 指向 `emoji_img/{code}.png` 的 img 标签（[`crate::rewrite::rewrite_emoji`] 生成的格式），
+//! 换回 alt 中的 emoji；ZWJ 序列拆分显示时后续图片的 alt 为空，直接删除。
+//! 原文为数字字符引用时还原为字符本身。
+
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::Path;
+
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::naming;
+use crate::replacer::find_opf_path_from_container;
+
+/// 还原结果汇总
+#[derive(Clone, Debug, Default)]
+pub struct RestoreReport {
+    /// 换回文字的 emoji 数
+    pub restored: usize,
+    /// 有改动的 xhtml/html 文件数
+    pub files: usize,
+    /// 删除的 emoji 图片数
+    pub images_removed: usize,
+}
+
+/// 把 content 中的 emoji 图片换回文字，返回新内容与还原的 emoji 数
+pub fn restore_emoji(content: &str) -> (String, usize) {
+    let mut result = String::with_capacity(content.len());
+    let mut restored = 0;
+    let mut rest = content;
+    while let Some(start) = rest.find("<img ") {
+        let Some(len) = rest[start..].find('>') else { break };
+        let tag = &rest[start..start + len + 1];
+        let Some(alt) = emoji_img_alt(tag) else {
+            result.push_str(&rest[..start + len + 1]);
+            rest = &rest[start + len + 1..];
+            continue;
+        };
+        let mut before = &rest[..start];
+        let mut after = &rest[start + len + 1..];
+        // 去掉插入时在标签前后加的换行
+        if before.ends_with('\n') && after.starts_with('\n') {
+            before = &before[..before.len() - 1];
+            after = &after[1..];
+        }
+        result.push_str(before);
+        result.push_str(alt);
+        if !alt.is_empty() {
+            restored += 1;
+        }
+        rest = after;
+    }
+    result.push_str(rest);
+    (result, restored)
+}
+
+/// 本工具插入的 img 标签返回其 alt，其他标签返回 None
+fn emoji_img_alt(tag: &str) -> Option<&str> {
+    let src = attribute(tag, "src")?;
+    let mut parts = src.rsplit('/');
+    let filename = parts.next()?;
+    if parts.next()? != "emoji_img" {
+        return None;
+    }
+    naming::code_from_book_filename(filename)?;
+    Some(attribute(tag, "alt").unwrap_or(""))
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+/// 还原 epub 文件，处理成功后才写出输出文件
+pub fn restore_emoji_in_epub(input_path: &str, output_path: &str) -> Result<RestoreReport, String> {
+    crate::log_info!("打开输入文件: {}", input_path);
+    let input_file = File::open(input_path).map_err(|e| format!("打开输入文件失败: {}", e))?;
+    let mut output = Cursor::new(Vec::new());
+    let report = restore_emoji_in_epub_stream(input_file, &mut output)?;
+    crate::log_info!("写入输出文件: {}", output_path);
+    std::fs::write(output_path, output.into_inner()).map_err(|e| format!("创建输出文件失败: {}", e))?;
+    Ok(report)
+}
+
+/// 从任意 `Read + Seek` 读取 epub 并还原，结果写入任意 `Write + Seek`
+pub fn restore_emoji_in_epub_stream<R: Read + Seek, W: Write + Seek>(input: R, output: W) -> Result<RestoreReport, String> {
+    let mut zip = ZipArchive::new(input).map_err(|e| format!("解析epub为zip失败: {}", e))?;
+    let opf_path = find_opf_path_from_container(&mut zip);
+    // emoji_img 目录在 opf 同级目录
+    let opf_dir = opf_path
+        .as_deref()
+        .and_then(|p| Path::new(p).parent())
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+    let emoji_dir = if opf_dir.is_empty() { "emoji_img/".to_string() } else { format!("{}/emoji_img/", opf_dir) };
+
+    let mut report = RestoreReport::default();
+    let mut writer = ZipWriter::new(output);
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i).map_err(|e| format!("读取zip第{}个文件失败: {}", i, e))?;
+        let name = file.name().to_string();
+        if name.starts_with(&emoji_dir) {
+            crate::log_debug!("删除emoji图片文件: {}", name);
+            report.images_removed += 1;
+            continue;
+        }
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf).map_err(|e| format!("读取文件内容失败: {}", e))?;
+        if name.ends_with(".xhtml") || name.ends_with(".html") {
+            if let Ok(content) = String::from_utf8(buf.clone()) {
+                let (content, restored) = restore_emoji(&content);
+                if restored > 0 || content.len() != buf.len() {
+                    crate::log_info!("文件={}，还原 {} 个emoji", name, restored);
+                    report.restored += restored;
+                    report.files += 1;
+                    buf = content.into_bytes();
+                }
+            }
+        } else if Some(&name) == opf_path.as_ref() {
+            if let Ok(content) = String::from_utf8(buf.clone()) {
+                crate::log_debug!("更新opf清单: {}", name);
+                buf = remove_emoji_manifest_items(&content).into_bytes();
+            }
+        }
+        writer.start_file(&name, options).map_err(|e| format!("写入zip文件失败: {}", e))?;
+        writer.write_all(&buf).map_err(|e| format!("写入zip内容失败: {}", e))?;
+    }
+    writer.finish().map_err(|e| format!("zip写入完成失败: {}", e))?;
+    crate::log_info!("还原完成: {} 个emoji，删除 {} 张图片", report.restored, report.images_removed);
+    Ok(report)
+}
+
+/// 删除 manifest 中的 emoji 图片条目（连同其前面的空白），其余内容原样保留
+fn remove_emoji_manifest_items(opf_content: &str) -> String {
+    let mut reader = Reader::from_str(opf_content);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut pending_whitespace = None;
+    loop {
+        let event = match reader.read_event() {
+            Ok(Event::Eof) => break,
+            Ok(event) => event,
+            Err(_) => return opf_content.to_string(),
+        };
+        match event {
+            Event::Text(ref t) if t.iter().all(u8::is_ascii_whitespace) => {
+                pending_whitespace = Some(event.into_owned());
+                continue;
+            }
+            Event::Empty(ref e) if e.name().as_ref() == b"item" && is_emoji_item(e) => {
+                pending_whitespace = None;
+                continue;
+            }
+            _ => {}
+        }
+        for ev in pending_whitespace.take().into_iter().chain(std::iter::once(event)) {
+            if writer.write_event(ev).is_err() {
+                return opf_content.to_string();
+            }
+        }
+    }
+    if let Some(ev) = pending_whitespace {
+        let _ = writer.write_event(ev);
+    }
+    String::from_utf8(writer.into_inner().into_inner()).unwrap_or_else(|_| opf_content.to_string())
+}
+
+fn is_emoji_item(e: &quick_xml::events::BytesStart) -> bool {
+    let mut id = false;
+    let mut href = false;
+    for attr in e.attributes().flatten() {
+        match attr.key.as_ref() {
+            b"id" => id = attr.value.starts_with(b"emoji_"),
+            b"href" => href = attr.value.starts_with(b"emoji_img/"),
+            _ => {}
+        }
+    }
+    id && href
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::CallbackSource;
+    use crate::replacer::{replace_emoji_in_epub_with_source, ReplaceOptions};
+    use crate::testutil;
+
+    #[test]
+    fn restores_alt_and_drops_split_component_images() {
+        let content = "<p>a\n<img alt=\"😀\" src=\"../emoji_img/1f600.png\" style=\"height:1em\"/>\nb\
+                       \n<img alt=\"👩\u{200d}💻\" src=\"../emoji_img/1f469.png\" style=\"\"/>\n\
+                       \n<img alt=\"\" src=\"../emoji_img/1f4bb.png\" style=\"\"/>\n</p>";
+        let (restored, n) = restore_emoji(content);
+        assert_eq!(restored, "<p>a😀b👩\u{200d}💻</p>");
+        assert_eq!(n, 2);
+    }
+
+    #[test]
+    fn other_images_are_kept() {
+        let content = "<img alt=\"cover\" src=\"../Images/cover.png\"/><img alt=\"x\" src=\"emoji_img/readme.txt\"/>";
+        assert_eq!(restore_emoji(content), (content.to_string(), 0));
+    }
+
+    #[test]
+    fn process_then_restore_round_trips() {
+        let original = testutil::epub("<p title=\"😀\">Hi 😀 and 👩\u{200d}💻 and ❤\u{fe0f} &#x1F600;</p>");
+        let mut source = CallbackSource::new(|_: &str| Some(b"png".to_vec()));
+        let mut processed = Cursor::new(Vec::new());
+        replace_emoji_in_epub_with_source(Cursor::new(original.clone()), &mut processed, &ReplaceOptions::default(), &mut source)
+            .unwrap();
+        let processed = processed.into_inner();
+        assert!(testutil::entry_names(&processed).contains(&"OEBPS/emoji_img/1f600.png".to_string()));
+
+        let mut restored = Cursor::new(Vec::new());
+        let report = restore_emoji_in_epub_stream(Cursor::new(processed), &mut restored).unwrap();
+        let restored = restored.into_inner();
+        assert_eq!((report.restored, report.files, report.images_removed), (4, 1, 3));
+        assert_eq!(testutil::entry_names(&restored), testutil::entry_names(&original));
+        let chapter = testutil::read_entry(&original, "OEBPS/Text/c1.xhtml").unwrap().replace("&#x1F600;", "😀");
+        assert_eq!(testutil::read_entry(&restored, "OEBPS/Text/c1.xhtml").unwrap(), chapter);
+        // 处理时 opf 会重新排版，只比较清单条目
+        let items = |epub: &[u8]| {
+            let opf = testutil::read_entry(epub, "OEBPS/content.opf").unwrap();
+            opf.match_indices("<item ").map(|(i, _)| opf[i..i + opf[i..].find('>').unwrap()].to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(items(&restored), items(&original));
+    }
+}
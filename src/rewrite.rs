@@ -0,0 +1,148 @@
+//! 替换核心：只改写标记，不访问文件系统与网络
+//!
+//! 图片的查找/下载由调用方通过 [`crate::provider::acquire_images`] 等方式完成，
+//! 结果作为 code -> [`ResolvedImage`] 传入。
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::charref::DecodedText;
+use crate::detect::{is_emoji_grapheme, DetectionPolicy};
+use crate::naming;
+use crate::normalize::regional_indicator_letters;
+use crate::provider::{ImageFallback, ResolvedImage};
+
+/// 改写结果
+#[derive(Clone, Debug, Default)]
+pub struct Rewritten {
+    pub content: String,
+    /// 引用到的图片 code，文件名见 [`naming::book_filename`]
+    pub assets: BTreeSet<String>,
+}
+
+/// img 标签的默认 style
+pub const DEFAULT_IMG_STYLE: &str = "height:1.3em";
+
+/// 把 content 文本节点中的 emoji 替换为指向 imgdir 的 img 标签，style 为 img 的 style 属性
+///
+/// images 中没有的 code 直接使用 code 本身的图片；回退结果为 [`ImageFallback::Missing`] 的保留原文字。
+pub fn rewrite_emoji(
+    content: &str,
+    imgdir: &str,
+    style: &str,
+    policy: &DetectionPolicy,
+    images: &HashMap<String, ResolvedImage>,
+) -> Rewritten {
+    let mut result = Rewritten::default();
+    let imgdir = imgdir.replace("\\", "/");
+    // 先解码数字字符引用，非 emoji 部分输出源文本
+    let decoded = DecodedText::new(content);
+    for piece in decoded.pieces() {
+        let (g, source) = (piece.text, piece.source);
+        if piece.markup {
+            result.content.push_str(source);
+        } else if is_emoji_grapheme(g, policy) {
+            let code = naming::grapheme_code(g, policy.filter.data.as_deref());
+            let codes = match images.get(&code) {
+                // 没有可用的图片时保留原文字
+                Some(image) if image.fallback == ImageFallback::Missing => {
+                    result.content.push_str(source);
+                    continue;
+                }
+                Some(image) => image.codes.clone(),
+                None => vec![code],
+            };
+            // 拆分显示时只在第一张图片上保留 alt，避免重复朗读
+            for (i, c) in codes.into_iter().enumerate() {
+                let alt = if i == 0 { g } else { "" };
+                let img_tag = format!(
+                    "\n<img alt=\"{}\" src=\"{}/{}\" style=\"{}\"/>\n",
+                    alt,
+                    imgdir,
+                    naming::book_filename(&c),
+                    style
+                );
+                result.content.push_str(&img_tag);
+                result.assets.insert(c);
+            }
+        } else if let Some(letters) = regional_indicator_letters(g) {
+            // 单个或无效的区域指示符显示为字母
+            result.content.push_str(&letters);
+        } else {
+            result.content.push_str(source);
+        }
+    }
+    result
+}
+
+/// content 的文本节点中是否有显示为字母的区域指示符（单个或无效组合）
+///
+/// 这类字符不算 emoji，没有 emoji 的文件也需要改写。
+pub fn has_regional_indicator_letters(content: &str) -> bool {
+    let maybe = content.contains("&#") || content.chars().any(|c| ('\u{1f1e6}'..='\u{1f1ff}').contains(&c));
+    maybe && DecodedText::new(content).pieces().any(|p| !p.markup && regional_indicator_letters(p.text).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(content: &str, images: &HashMap<String, ResolvedImage>) -> Rewritten {
+        rewrite_emoji(content, "../Images", "height:1em", &DetectionPolicy::default(), images)
+    }
+
+    fn img(alt: &str, code: &str) -> String {
+        format!("\n<img alt=\"{}\" src=\"../Images/{}.png\" style=\"height:1em\"/>\n", alt, code)
+    }
+
+    #[test]
+    fn emoji_in_text_becomes_img() {
+        let out = rewrite("<p>早 😀</p>", &HashMap::new());
+        assert_eq!(out.content, format!("<p>早 {}</p>", img("😀", "1f600")));
+        assert_eq!(out.assets.into_iter().collect::<Vec<_>>(), ["1f600"]);
+    }
+
+    #[test]
+    fn markup_is_left_untouched() {
+        let content = r#"<p title="😀 &#x1F600;"><!-- 😀 --><![CDATA[😀]]></p>"#;
+        let out = rewrite(content, &HashMap::new());
+        assert_eq!(out.content, content);
+        assert!(out.assets.is_empty());
+    }
+
+    #[test]
+    fn character_reference_is_replaced() {
+        let out = rewrite("a&#x1F600;&#60;", &HashMap::new());
+        assert_eq!(out.content, format!("a{}&#60;", img("😀", "1f600")));
+    }
+
+    #[test]
+    fn missing_image_keeps_source_text() {
+        let images = HashMap::from([(
+            "1f600".to_string(),
+            ResolvedImage { fallback: ImageFallback::Missing, codes: vec!["1f600".to_string()] },
+        )]);
+        let out = rewrite("a&#x1F600;b", &images);
+        assert_eq!(out.content, "a&#x1F600;b");
+        assert!(out.assets.is_empty());
+    }
+
+    #[test]
+    fn components_keep_alt_on_first_image_only() {
+        let images = HashMap::from([(
+            "1f469-200d-1f4bb".to_string(),
+            ResolvedImage { fallback: ImageFallback::Components, codes: vec!["1f469".to_string(), "1f4bb".to_string()] },
+        )]);
+        let out = rewrite("👩\u{200d}💻", &images);
+        assert_eq!(out.content, format!("{}{}", img("👩\u{200d}💻", "1f469"), img("", "1f4bb")));
+    }
+
+    #[test]
+    fn invalid_regional_indicators_become_letters() {
+        assert!(has_regional_indicator_letters("<p>🇦</p>"));
+        assert!(has_regional_indicator_letters("<p>&#x1F1E6;</p>"));
+        assert!(!has_regional_indicator_letters("<p>🇨🇳</p>"));
+        assert!(!has_regional_indicator_letters("<p title=\"🇦\">x</p>"));
+        let out = rewrite("<p>🇦 🇦🇦</p>", &HashMap::new());
+        assert_eq!(out.content, "<p>A AA</p>");
+    }
+}
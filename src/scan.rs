@@ -0,0 +1,213 @@
+//! emoji 扫描：只报告位置与信息，不改动内容
+//!
+//! 识别规则与替换时一致（只看文本节点，含数字字符引用解码与 [`DetectionPolicy`]）。
+
+use std::io::{Read, Seek};
+use std::ops::Range;
+
+use emojis::Group;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::charref::DecodedText;
+use crate::detect::{is_emoji_grapheme, DetectionPolicy};
+use crate::naming;
+use crate::replacer::{book_policy, is_content_file, BookLayout};
+
+/// 一处 emoji
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmojiMatch {
+    /// 源文本中的字节范围，字符引用形式时覆盖整个引用
+    pub range: Range<usize>,
+    /// 行号，从 1 开始
+    pub line: usize,
+    /// 列号（按字符计），从 1 开始
+    pub column: usize,
+    /// 字素簇（字符引用已解码）
+    pub grapheme: String,
+    /// 规范 code，即图片文件名，见 [`naming::grapheme_code`]
+    pub code: String,
+    /// 字素簇的原始码点
+    pub codepoints: Vec<u32>,
+    /// CLDR 名称，如 `grinning face`
+    pub name: Option<String>,
+    /// 分组，如 `Smileys & Emotion`
+    pub group: Option<String>,
+    /// Emoji 版本，如 (15, 1)
+    pub version: Option<(u32, u32)>,
+}
+
+/// 按 policy 扫描 text 中的 emoji
+pub fn scan<'a>(text: &'a str, policy: &'a DetectionPolicy) -> Scan<'a> {
+    let decoded = DecodedText::new(text);
+    let ranges = decoded.text_ranges().collect::<Vec<_>>().into_iter();
+    Scan { decoded, policy, ranges, current: 0..0, line: 1, line_start: 0, counted: 0 }
+}
+
+/// [`scan`] 返回的迭代器
+pub struct Scan<'a> {
+    decoded: DecodedText<'a>,
+    policy: &'a DetectionPolicy,
+    /// 尚未扫描的文本节点（解码文本中的范围）
+    ranges: std::vec::IntoIter<Range<usize>>,
+    /// 当前文本节点中尚未扫描的部分
+    current: Range<usize>,
+    line: usize,
+    /// 当前行在源文本中的起始位置
+    line_start: usize,
+    /// 源文本中已统计换行的位置
+    counted: usize,
+}
+
+impl Iterator for Scan<'_> {
+    type Item = EmojiMatch;
+
+    fn next(&mut self) -> Option<EmojiMatch> {
+        loop {
+            if self.current.is_empty() {
+                self.current = self.ranges.next()?;
+            }
+            let g = self.decoded.text()[self.current.clone()].graphemes(true).next()?.to_string();
+            let decoded_range = self.current.start..self.current.start + g.len();
+            self.current.start = decoded_range.end;
+            if !is_emoji_grapheme(&g, self.policy) {
+                continue;
+            }
+            let range = self.decoded.source_range(decoded_range);
+            let source = self.source();
+            for (i, _) in source[self.counted..range.start].match_indices('\n') {
+                self.line += 1;
+                self.line_start = self.counted + i + 1;
+            }
+            self.counted = range.start;
+            let column = source[self.line_start..range.start].chars().count() + 1;
+            return Some(self.describe(g, range, column));
+        }
+    }
+}
+
+impl<'a> Scan<'a> {
+    fn source(&self) -> &'a str {
+        self.decoded.source()
+    }
+
+    fn describe(&self, grapheme: String, range: Range<usize>, column: usize) -> EmojiMatch {
+        let data = self.policy.filter.data.as_deref();
+        let code = naming::grapheme_code(&grapheme, data);
+        let without_fe0e = naming::strip_text_selector(&grapheme);
+        let (name, group, version) = match emojis::get(&without_fe0e).or_else(|| emojis::get(&naming::strip_selectors(&grapheme))) {
+            Some(e) => (
+                Some(e.name().to_string()),
+                Some(group_name(e.group()).to_string()),
+                Some((e.unicode_version().major(), e.unicode_version().minor())),
+            ),
+            None => match data.and_then(|d| d.fully_qualified(&code)) {
+                Some(entry) => (Some(entry.name.clone()), Some(entry.group.clone()), entry.version),
+                None => (None, None, None),
+            },
+        };
+        EmojiMatch {
+            range,
+            line: self.line,
+            column,
+            codepoints: grapheme.chars().map(|c| c as u32).collect(),
+            grapheme,
+            code,
+            name,
+            group,
+            version,
+        }
+    }
+}
+
+/// 扫描 epub 中各 xhtml/html 文件，返回 (epub 内路径, 该文件中的 emoji)，跳过没有 emoji 的文件
+///
+/// 与替换时相同：跳过 nav 文件，启用 `embedded_fonts` 时使用书内字体判断覆盖。
+pub fn scan_epub<R: Read + Seek>(input: R, policy: &DetectionPolicy) -> Result<Vec<(String, Vec<EmojiMatch>)>, String> {
+    let mut zip = zip::ZipArchive::new(input).map_err(|e| format!("解析epub为zip失败: {}", e))?;
+    let layout = BookLayout::read(&mut zip);
+    let policy = book_policy(&mut zip, policy);
+    let mut files = Vec::new();
+    for i in 0..zip.len() {
+        let Ok(mut file) = zip.by_index(i) else { continue };
+        if !is_content_file(file.name(), &layout.nav_files) {
+            continue;
+        }
+        let mut content = String::new();
+        if file.read_to_string(&mut content).is_ok() {
+            let matches: Vec<EmojiMatch> = scan(&content, &policy).collect();
+            if !matches.is_empty() {
+                files.push((file.name().to_string(), matches));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// emoji-test.txt 中的分组名
+pub fn group_name(group: Group) -> &'static str {
+    match group {
+        Group::SmileysAndEmotion => "Smileys & Emotion",
+        Group::PeopleAndBody => "People & Body",
+        Group::AnimalsAndNature => "Animals & Nature",
+        Group::FoodAndDrink => "Food & Drink",
+        Group::TravelAndPlaces => "Travel & Places",
+        Group::Activities => "Activities",
+        Group::Objects => "Objects",
+        Group::Symbols => "Symbols",
+        Group::Flags => "Flags",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+    use std::io::Cursor;
+
+    fn positions(text: &str) -> Vec<(usize, usize, Range<usize>, String)> {
+        scan(text, &DetectionPolicy::default()).map(|m| (m.line, m.column, m.range, m.code)).collect()
+    }
+
+    #[test]
+    fn reports_lines_columns_and_byte_ranges() {
+        let text = "第一行 😀\n&#x1F469;&#x200D;&#x1F4BB;";
+        assert_eq!(
+            positions(text),
+            [(1, 5, 10..14, "1f600".to_string()), (2, 1, 15..41, "1f469-200d-1f4bb".to_string())]
+        );
+        assert_eq!(&text[15..41], "&#x1F469;&#x200D;&#x1F4BB;");
+    }
+
+    #[test]
+    fn columns_count_markup_characters_and_lines_reset() {
+        let text = "<p class=\"x\">😀</p>\n\n  <p>a😀</p>";
+        assert_eq!(
+            positions(text),
+            [(1, 14, 13..17, "1f600".to_string()), (3, 7, 29..33, "1f600".to_string())]
+        );
+    }
+
+    #[test]
+    fn attributes_are_not_scanned() {
+        assert!(positions("<img alt=\"😀\"/><!-- 😀 -->").is_empty());
+    }
+
+    #[test]
+    fn describes_emoji() {
+        let m = scan("❤\u{fe0f}", &DetectionPolicy::default()).next().unwrap();
+        assert_eq!(m.grapheme, "❤\u{fe0f}");
+        assert_eq!(m.codepoints, [0x2764, 0xfe0f]);
+        assert_eq!(m.name.as_deref(), Some("red heart"));
+        assert_eq!(m.group.as_deref(), Some("Smileys & Emotion"));
+        assert_eq!(m.version, Some((0, 6)));
+    }
+
+    #[test]
+    fn scan_epub_skips_nav() {
+        let epub = testutil::epub("<p>😀</p>");
+        let files = scan_epub(Cursor::new(epub), &DetectionPolicy::default()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "OEBPS/Text/c1.xhtml");
+        assert_eq!(files[0].1.len(), 1);
+    }
+}
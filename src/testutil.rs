@@ -0,0 +1,49 @@
+//! 测试用的内存 epub
+
+use std::io::{Cursor, Read, Write};
+
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const CONTAINER: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+
+const OPF: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0"><metadata/><manifest><item id="c1" href="Text/c1.xhtml" media-type="application/xhtml+xml"/><item id="nav" href="nav.xhtml" properties="nav" media-type="application/xhtml+xml"/></manifest><spine><itemref idref="c1"/></spine></package>"#;
+
+/// 包含 OEBPS/Text/c1.xhtml（body 为 body）与 nav 的 epub
+pub fn epub(body: &str) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let chapter = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<html xmlns=\"http://www.w3.org/1999/xhtml\"><body>{}</body></html>",
+        body
+    );
+    let files = [
+        ("mimetype", "application/epub+zip"),
+        ("META-INF/container.xml", CONTAINER),
+        ("OEBPS/content.opf", OPF),
+        ("OEBPS/Text/c1.xhtml", chapter.as_str()),
+        ("OEBPS/nav.xhtml", "<nav>😀</nav>"),
+    ];
+    for (name, content) in files {
+        writer.start_file(name, FileOptions::default()).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+/// 读取 epub 中的文件，不存在时返回 None
+pub fn read_entry(epub: &[u8], name: &str) -> Option<String> {
+    let mut zip = ZipArchive::new(Cursor::new(epub)).unwrap();
+    let mut file = zip.by_name(name).ok()?;
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    Some(content)
+}
+
+/// epub 中的文件名（已排序）
+pub fn entry_names(epub: &[u8]) -> Vec<String> {
+    let mut names: Vec<String> = ZipArchive::new(Cursor::new(epub)).unwrap().file_names().map(str::to_string).collect();
+    names.sort();
+    names
+}
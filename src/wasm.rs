@@ -0,0 +1,58 @@
+//! WebAssembly 绑定（`wasm` feature）
+//!
+//! 浏览器中没有文件系统与阻塞网络，图片由 JS 回调提供：
+//! `provider(code: string) => Uint8Array | null | undefined`，code 为图片文件名去掉 `.png`。
+//! 回调必须是同步的，可先用 [`emoji_codes`] 取得需要的图片，异步下载后再调用 [`replace_emoji_in_epub`]。
+
+use std::path::Path;
+
+use wasm_bindgen::prelude::*;
+
+use crate::config::apply_config;
+use crate::provider::{resolve_image_from, CallbackSource};
+use crate::replacer::{collect_emoji_codes, replace_emoji_in_epub_with_source, ReplaceOptions};
+
+/// config 为配置文件内容（key = value），涉及文件路径的选项不可用
+fn options(config: Option<String>) -> Result<ReplaceOptions, JsValue> {
+    let mut opts = ReplaceOptions::default();
+    if let Some(content) = config {
+        apply_config(&content, Path::new(""), &mut opts).map_err(|e| JsValue::from_str(&e))?;
+    }
+    Ok(opts)
+}
+
+fn js_source(provider: &js_sys::Function) -> CallbackSource<impl FnMut(&str) -> Option<Vec<u8>> + '_> {
+    CallbackSource::new(move |code: &str| {
+        let value = provider.call1(&JsValue::NULL, &JsValue::from_str(code)).ok()?;
+        if value.is_null() || value.is_undefined() {
+            return None;
+        }
+        Some(js_sys::Uint8Array::new(&value).to_vec())
+    })
+}
+
+/// 处理内存中的 epub，返回新 epub 的字节
+#[wasm_bindgen(js_name = replaceEmojiInEpub)]
+pub fn replace_emoji_in_epub(input: &[u8], provider: &js_sys::Function, config: Option<String>) -> Result<Vec<u8>, JsValue> {
+    let opts = options(config)?;
+    let mut source = js_source(provider);
+    let mut output = std::io::Cursor::new(Vec::new());
+    replace_emoji_in_epub_with_source(std::io::Cursor::new(input), &mut output, &opts, &mut source)
+        .map_err(|e| JsValue::from_str(&e))?;
+    Ok(output.into_inner())
+}
+
+/// epub 中各 xhtml/html 出现的 emoji 对应的图片 code（去重），供 JS 预先下载
+#[wasm_bindgen(js_name = emojiCodes)]
+pub fn emoji_codes(input: &[u8], config: Option<String>) -> Result<Vec<String>, JsValue> {
+    let opts = options(config)?;
+    let codes = collect_emoji_codes(std::io::Cursor::new(input), &opts.detection).map_err(|e| JsValue::from_str(&e))?;
+    Ok(codes.into_iter().collect())
+}
+
+/// 按回退链（完全限定 → 去掉变体选择符 → 去掉肤色 → 拆分 ZWJ）为 code 找到可用的图片，
+/// 返回实际使用的 code 列表；provider 返回 null 表示没有该图片
+#[wasm_bindgen(js_name = resolveImage)]
+pub fn resolve_image(code: &str, provider: &js_sys::Function) -> Vec<String> {
+    resolve_image_from(&mut js_source(provider), code).codes
+}